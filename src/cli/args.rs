@@ -1,13 +1,34 @@
-use clap::{Args, Parser, Subcommand};
+use crate::domain::dates;
+use crate::domain::models::{
+    Estimate, Priority, Recurrence, Schedule, Status, parse_project, parse_tag,
+};
+use chrono::{DateTime, Local};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(name = "rtask")]
 #[command(about = "CLI for managing Tasks, Habits, and Pomodoro sessions", long_about = None)]
 pub struct Cli {
+    /// Storage backend to use [default: storage.backend from config, json]
+    #[arg(long, global = true, value_enum)]
+    pub backend: Option<Backend>,
+
+    /// Directory holding the data files (overrides RTASK_DATA_DIR and the config file)
+    #[arg(long, global = true)]
+    pub data_dir: Option<String>,
+
     #[command(subcommand)]
     pub command: EntityCommands,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// JSON files (tasks.json, habits.json, current_session.json)
+    Json,
+    /// SQLite database (rtask.db)
+    Sqlite,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum EntityCommands {
     /// Manage Tasks
@@ -16,30 +37,52 @@ pub enum EntityCommands {
     Habit(HabitCommand),
     /// Manage Pomodoro
     Pomodoro(PomodoroCommand),
+    /// Summarize task projects
+    Project(ProjectCommand),
+    /// Show the most urgent tasks that can be worked on now
+    Next(NextArgs),
+    /// Report time tracked on tasks
+    Time(TimeCommand),
+    /// Statistics over the stored data
+    Stats(StatsCommand),
+    /// Manage the storage backends
+    Storage(StorageCommand),
+    /// Read and change settings in the config file
+    Config(ConfigCommand),
+    /// Show how a date expression is interpreted
+    CalcDate {
+        /// Date expression, e.g. "next monday 9am"
+        #[arg(required = true)]
+        expr: Vec<String>,
+    },
 }
 
 #[derive(Args, Debug)]
 pub struct TaskCommand {
     #[command(subcommand)]
-    pub action: CrudActions,
+    pub action: TaskActions,
 }
 
 #[derive(Args, Debug)]
 pub struct HabitCommand {
     #[command(subcommand)]
-    pub action: CrudActions,
+    pub action: HabitActions,
 }
 
 #[derive(Subcommand, Debug)]
-pub enum CrudActions {
-    /// Create a new item
+pub enum HabitActions {
+    /// Create a new habit
     Enter(EnterArgs),
-    /// Delete an item by ID
+    /// Delete a habit by ID
     Delete(DeleteArgs),
-    /// Read/List items
+    /// List habits with their streaks
     Read,
-    /// Update an item
+    /// Update a habit
     Update(UpdateArgs),
+    /// Record a habit as done, today or on a past day
+    Check(HabitCheckArgs),
+    /// Remove the check-in of a day
+    Uncheck(HabitUncheckArgs),
 }
 
 #[derive(Args, Debug)]
@@ -52,9 +95,18 @@ pub struct EnterArgs {
     #[arg(short, long)]
     pub description: Option<String>,
 
-    /// Frequency (for Habits only)
+    /// Schedule: daily, "every 2 days", 3/week, 2/month, mon,wed,fri or weekdays
+    /// [default: habit.frequency from config, daily]
     #[arg(short, long)]
-    pub frequency: Option<String>,
+    pub frequency: Option<Schedule>,
+
+    /// Amount to reach each period, e.g. 20 for "read 20 pages"
+    #[arg(long)]
+    pub target: Option<f64>,
+
+    /// What the target counts, e.g. pages
+    #[arg(long, requires = "target")]
+    pub unit: Option<String>,
 }
 
 #[derive(Args, Debug)]
@@ -78,15 +130,335 @@ pub struct UpdateArgs {
     #[arg(short, long)]
     pub description: Option<String>,
 
-    /// Mark as completed (for Tasks)
+    /// New schedule: daily, "every 2 days", 3/week, 2/month, mon,wed,fri or weekdays
     #[arg(short, long)]
-    pub completed: bool,
+    pub frequency: Option<Schedule>,
 
-    /// Increment streak (for Habits)
-    #[arg(short, long)]
+    /// New amount to reach each period
+    #[arg(long, conflicts_with = "no_target")]
+    pub target: Option<f64>,
+
+    /// New unit of the target
+    #[arg(long, conflicts_with = "no_target")]
+    pub unit: Option<String>,
+
+    /// Drop the target, counting check-ins again
+    #[arg(long)]
+    pub no_target: bool,
+
+    /// Check in for today; a period already checked in stays as it is
+    #[arg(long)]
     pub increment: bool,
 }
 
+#[derive(Args, Debug)]
+pub struct HabitCheckArgs {
+    /// ID of the habit
+    #[arg(short, long)]
+    pub id: u32,
+
+    /// Day of the check-in, e.g. yesterday, "last fri", 2026-10-15 [default: today]
+    #[arg(long, value_parser = parse_date_arg)]
+    pub date: Option<DateTime<Local>>,
+
+    /// Amount done, for habits with a target; adds to the day's total
+    #[arg(short, long)]
+    pub amount: Option<f64>,
+}
+
+#[derive(Args, Debug)]
+pub struct HabitUncheckArgs {
+    /// ID of the habit
+    #[arg(short, long)]
+    pub id: u32,
+
+    /// Day to clear, with its whole amount [default: today]
+    #[arg(long, value_parser = parse_date_arg)]
+    pub date: Option<DateTime<Local>>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TaskActions {
+    /// Create a new task
+    Enter(TaskEnterArgs),
+    /// Delete a task by ID, or every task matching a filter
+    Delete(TaskDeleteArgs),
+    /// List tasks, soonest due first
+    Read(TaskReadArgs),
+    /// Update a task by ID, or every task matching a filter
+    Update(TaskUpdateArgs),
+    /// Mark tasks as in progress; with --id also start timing the task
+    Start(TaskStatusArgs),
+    /// Stop the running task timer
+    Stop,
+    /// Mark tasks as done
+    Done(TaskStatusArgs),
+    /// Mark tasks as cancelled
+    Cancel(TaskStatusArgs),
+    /// Move done or cancelled tasks back to todo
+    Reopen(TaskStatusArgs),
+    /// Add a timestamped note to a task
+    Annotate(TaskAnnotateArgs),
+    /// Remove a note from a task
+    Denotate(TaskDenotateArgs),
+    /// Show every detail of a task, with its notes and history
+    Show(TaskShowArgs),
+    /// Hide a task from `read` and `next` until a date
+    Snooze(TaskSnoozeArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TaskEnterArgs {
+    /// Title
+    #[arg(short, long)]
+    pub title: Option<String>,
+
+    /// Description
+    #[arg(short, long)]
+    pub description: Option<String>,
+
+    /// Priority: H, M or L
+    #[arg(short, long)]
+    pub priority: Option<Priority>,
+
+    /// Due date, e.g. "tomorrow", "fri 5pm", "in 3 days", 2026-11-02
+    #[arg(long, value_parser = parse_date_arg)]
+    pub due: Option<DateTime<Local>>,
+
+    /// Repeat rule: daily, weekly, monthly, "every 3 days", mon,wed,fri or an RRULE
+    #[arg(short, long)]
+    pub recur: Option<Recurrence>,
+
+    /// Tag to attach, repeatable
+    #[arg(long, value_parser = parse_tag)]
+    pub tag: Vec<String>,
+
+    /// Project, dotted for subprojects, e.g. work.backend
+    #[arg(long, value_parser = parse_project)]
+    pub project: Option<String>,
+
+    /// Make this a subtask of the given task ID
+    #[arg(long)]
+    pub parent: Option<u32>,
+
+    /// ID of a task that must be done first, repeatable
+    #[arg(long)]
+    pub depends_on: Vec<u32>,
+
+    /// Expected effort, e.g. 90m, 1h30m, 1.5h or 4p (pomodoros)
+    #[arg(short, long)]
+    pub estimate: Option<Estimate>,
+}
+
+#[derive(Args, Debug)]
+pub struct TaskReadArgs {
+    /// Filter terms, e.g. status:pending project:work due.before:fri +urgent "text";
+    /// put exclusions like -urgent after `--`
+    pub filter: Vec<String>,
+
+    /// Only tasks with this tag, or without it when prefixed with '-'; repeatable
+    #[arg(long, allow_hyphen_values = true)]
+    pub tag: Vec<String>,
+
+    /// Only tasks in this project or its subprojects
+    #[arg(long, value_parser = parse_project)]
+    pub project: Option<String>,
+
+    /// Only open tasks whose dependencies are done
+    #[arg(long, conflicts_with = "blocked")]
+    pub ready: bool,
+
+    /// Only tasks waiting on other tasks, with what they wait on
+    #[arg(long)]
+    pub blocked: bool,
+
+    /// Include snoozed tasks
+    #[arg(short, long)]
+    pub all: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct TaskDeleteArgs {
+    /// ID of the task to delete
+    #[arg(short, long, required_unless_present = "filter")]
+    pub id: Option<u32>,
+
+    /// Filter terms selecting the tasks to delete, see `task read`
+    #[arg(conflicts_with = "id")]
+    pub filter: Vec<String>,
+
+    /// Don't ask for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Delete subtasks too instead of refusing
+    #[arg(long)]
+    pub cascade: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct TaskStatusArgs {
+    /// ID of the task
+    #[arg(short, long, required_unless_present = "filter")]
+    pub id: Option<u32>,
+
+    /// Filter terms selecting the tasks, see `task read`
+    #[arg(conflicts_with = "id")]
+    pub filter: Vec<String>,
+
+    /// Don't ask for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// When closing, give open subtasks the same status instead of refusing
+    #[arg(long)]
+    pub cascade: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct TaskAnnotateArgs {
+    /// ID of the task
+    #[arg(short, long)]
+    pub id: u32,
+
+    /// Note text
+    #[arg(required = true)]
+    pub text: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct TaskDenotateArgs {
+    /// ID of the task
+    #[arg(short, long)]
+    pub id: u32,
+
+    /// Number of the note, as listed by `task show`
+    #[arg(short, long)]
+    pub number: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct TaskShowArgs {
+    /// ID of the task
+    #[arg(short, long)]
+    pub id: u32,
+}
+
+#[derive(Args, Debug)]
+pub struct TaskSnoozeArgs {
+    /// ID of the task
+    #[arg(short, long)]
+    pub id: u32,
+
+    /// When the task comes back, e.g. "next monday", "in 2 weeks", 2026-12-01
+    #[arg(required_unless_present = "clear", conflicts_with = "clear")]
+    pub until: Vec<String>,
+
+    /// Bring a snoozed task back now
+    #[arg(long)]
+    pub clear: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct TaskUpdateArgs {
+    /// ID of the task to update
+    #[arg(short, long, required_unless_present = "filter")]
+    pub id: Option<u32>,
+
+    /// Filter terms selecting the tasks to update, see `task read`
+    #[arg(conflicts_with = "id")]
+    pub filter: Vec<String>,
+
+    /// Don't ask for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// New Title
+    #[arg(short, long)]
+    pub title: Option<String>,
+
+    /// New Description
+    #[arg(short, long)]
+    pub description: Option<String>,
+
+    /// New status: todo, in-progress, waiting, done or cancelled
+    #[arg(short, long)]
+    pub status: Option<Status>,
+
+    /// New priority: H, M or L
+    #[arg(short, long, conflicts_with = "no_priority")]
+    pub priority: Option<Priority>,
+
+    /// Remove the priority
+    #[arg(long)]
+    pub no_priority: bool,
+
+    /// New due date, e.g. "tomorrow", "fri 5pm", "in 3 days", 2026-11-02
+    #[arg(long, value_parser = parse_date_arg, conflicts_with = "no_due")]
+    pub due: Option<DateTime<Local>>,
+
+    /// Remove the due date
+    #[arg(long)]
+    pub no_due: bool,
+
+    /// New repeat rule: daily, weekly, monthly, "every 3 days", mon,wed,fri or an RRULE
+    #[arg(short, long, conflicts_with = "no_recur")]
+    pub recur: Option<Recurrence>,
+
+    /// Stop repeating the task
+    #[arg(long)]
+    pub no_recur: bool,
+
+    /// Tag to attach, repeatable
+    #[arg(long, value_parser = parse_tag)]
+    pub tag: Vec<String>,
+
+    /// Tag to remove, repeatable
+    #[arg(long, value_parser = parse_tag)]
+    pub remove_tag: Vec<String>,
+
+    /// New project, dotted for subprojects, e.g. work.backend
+    #[arg(long, value_parser = parse_project, conflicts_with = "no_project")]
+    pub project: Option<String>,
+
+    /// Take the task out of its project
+    #[arg(long)]
+    pub no_project: bool,
+
+    /// Make this a subtask of the given task ID
+    #[arg(long, conflicts_with = "no_parent")]
+    pub parent: Option<u32>,
+
+    /// Make this a top-level task again
+    #[arg(long)]
+    pub no_parent: bool,
+
+    /// When closing, give open subtasks the same status instead of refusing
+    #[arg(long)]
+    pub cascade: bool,
+
+    /// ID of a task that must be done first, repeatable
+    #[arg(long)]
+    pub depends_on: Vec<u32>,
+
+    /// Dependency to drop, repeatable
+    #[arg(long)]
+    pub remove_depends_on: Vec<u32>,
+
+    /// New expected effort, e.g. 90m, 1h30m, 1.5h or 4p (pomodoros)
+    #[arg(short, long, conflicts_with = "no_estimate")]
+    pub estimate: Option<Estimate>,
+
+    /// Drop the estimate
+    #[arg(long)]
+    pub no_estimate: bool,
+}
+
+/// Parse a date argument such as `tomorrow`, `next monday 9am` or `2026-11-02`
+pub fn parse_date_arg(s: &str) -> Result<DateTime<Local>, String> {
+    dates::parse_date(s, Local::now())
+}
+
 #[derive(Args, Debug)]
 pub struct PomodoroCommand {
     #[command(subcommand)]
@@ -101,32 +473,154 @@ pub enum PomodoroActions {
     Delete,
     /// Check status
     Read,
-    // Update not strictly needed but could be pause/resume? keeping simple as per req
+    /// Finish the current work phase or break and move on to the next
+    Update,
 }
 
 #[derive(Args, Debug)]
 pub struct StartPomodoroArgs {
-    /// Work duration in minutes
-    #[arg(short, long, default_value = "25")]
-    pub work: u32,
+    /// Work duration in minutes [default: pomodoro.work from config, 25]
+    #[arg(short, long)]
+    pub work: Option<u32>,
 
-    /// Short break duration
-    #[arg(short = 'b', long, default_value = "5")]
-    pub short_break: u32,
+    /// Short break duration [default: pomodoro.short_break from config, 5]
+    #[arg(short = 'b', long)]
+    pub short_break: Option<u32>,
 
-    /// Long break duration
-    #[arg(short = 'B', long, default_value = "15")]
-    pub long_break: u32,
+    /// Long break duration [default: pomodoro.long_break from config, 15]
+    #[arg(short = 'B', long)]
+    pub long_break: Option<u32>,
 
-    /// Total sessions
-    #[arg(short = 's', long, default_value = "6")]
-    pub sessions: u32,
+    /// Total sessions [default: pomodoro.sessions from config, 6]
+    #[arg(short = 's', long)]
+    pub sessions: Option<u32>,
 
-    /// Sessions before long break
-    #[arg(short = 'l', long, default_value = "4")]
-    pub long_break_interval: u32,
+    /// Sessions before long break [default: pomodoro.long_break_interval from config, 4]
+    #[arg(short = 'l', long)]
+    pub long_break_interval: Option<u32>,
 
-    /// Associate with Task ID
+    /// Task the work phases count towards in `time report`
     #[arg(short = 't', long)]
     pub task_id: Option<u32>,
 }
+
+#[derive(Args, Debug)]
+pub struct TimeCommand {
+    #[command(subcommand)]
+    pub action: TimeActions,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TimeActions {
+    /// Sum up tracked time, from task timers and linked pomodoros
+    Report(TimeReportArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TimeReportArgs {
+    /// Only count time from this date on, e.g. "last mon", 2026-10-01 [default: all time]
+    #[arg(long, value_parser = parse_date_arg)]
+    pub since: Option<DateTime<Local>>,
+
+    /// Only count time before this date [default: now]
+    #[arg(long, value_parser = parse_date_arg)]
+    pub until: Option<DateTime<Local>>,
+
+    /// Group the time by task, project or tag
+    #[arg(long, value_enum, default_value = "task")]
+    pub by: ReportBy,
+}
+
+#[derive(Args, Debug)]
+pub struct StatsCommand {
+    #[command(subcommand)]
+    pub action: StatsActions,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StatsActions {
+    /// Compare task estimates with tracked time, per task, project and week
+    Estimates(StatsEstimatesArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct StatsEstimatesArgs {
+    /// Filter terms selecting the tasks, see `task read`
+    pub filter: Vec<String>,
+
+    /// Number of weeks in the accuracy trend
+    #[arg(short, long, default_value_t = 8)]
+    pub weeks: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportBy {
+    Task,
+    Project,
+    /// A task with several tags counts towards each
+    Tag,
+}
+
+#[derive(Args, Debug)]
+pub struct NextArgs {
+    /// Number of tasks to show
+    #[arg(short = 'n', long, default_value_t = 5)]
+    pub count: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct ProjectCommand {
+    #[command(subcommand)]
+    pub action: ProjectActions,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ProjectActions {
+    /// List projects with their open and done task counts
+    List,
+}
+
+#[derive(Args, Debug)]
+pub struct StorageCommand {
+    #[command(subcommand)]
+    pub action: StorageActions,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StorageActions {
+    /// Import the JSON files into the SQLite database
+    Migrate(MigrateArgs),
+    /// Report the schema version of the stored data
+    Check,
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateArgs {
+    /// Overwrite data already present in the SQLite database
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigCommand {
+    #[command(subcommand)]
+    pub action: ConfigActions,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigActions {
+    /// Print the value of a setting
+    Get {
+        /// Dotted key, e.g. pomodoro.work
+        key: String,
+    },
+    /// Change a setting and save the config file
+    Set {
+        /// Dotted key, e.g. pomodoro.work
+        key: String,
+        /// New value
+        value: String,
+    },
+    /// Print every setting
+    List,
+}
@@ -0,0 +1,500 @@
+use crate::domain::models::Schedule;
+use crate::domain::urgency::UrgencyWeights;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the data directory
+pub const DATA_DIR_ENV: &str = "RTASK_DATA_DIR";
+/// Data directory used by releases that stored everything next to the binary
+pub const LEGACY_DATA_DIR: &str = "./data";
+
+/// Settings read from `$XDG_CONFIG_HOME/rtask/config.toml`.
+///
+/// Every field has a default, so the file only needs the keys a user wants
+/// to change.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where tasks, habits and sessions are stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<String>,
+    pub pomodoro: PomodoroConfig,
+    pub habit: HabitConfig,
+    pub output: OutputConfig,
+    /// Weights of the score used by `rtask next`
+    pub urgency: UrgencyWeights,
+    pub storage: StorageConfig,
+}
+
+/// Defaults for `pomodoro enter`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PomodoroConfig {
+    /// Work duration in minutes
+    pub work: u32,
+    /// Short break duration in minutes
+    pub short_break: u32,
+    /// Long break duration in minutes
+    pub long_break: u32,
+    /// Total sessions
+    pub sessions: u32,
+    /// Sessions before a long break
+    pub long_break_interval: u32,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work: 25,
+            short_break: 5,
+            long_break: 15,
+            sessions: 6,
+            long_break_interval: 4,
+        }
+    }
+}
+
+/// Defaults for `habit enter`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HabitConfig {
+    /// Schedule used when none is given, e.g. daily or 3/week
+    pub frequency: Schedule,
+}
+
+/// Table layout for the `read` commands
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Width of the title/name column
+    pub title_width: usize,
+    /// Width of the description column
+    pub description_width: usize,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            title_width: 20,
+            description_width: 30,
+        }
+    }
+}
+
+/// Where the data is kept
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Backend used when `--backend` isn't given
+    pub backend: StorageBackend,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// JSON files
+    #[default]
+    Json,
+    /// SQLite database
+    Sqlite,
+}
+
+impl Config {
+    // A missing config file just means defaults everywhere
+    pub fn load() -> Result<Self, AppError> {
+        let path = match config_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Self::default()),
+        };
+        let text = fs::read_to_string(&path)
+            .map_err(|e| AppError::Storage(format!("Failed to read {}: {}", path.display(), e)))?;
+        let config: Config = toml::from_str(&text).map_err(|e| {
+            AppError::Validation(format!("Invalid config {}: {}", path.display(), e))
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load the config keeping every setting that is valid on its own, for
+    /// the `config` commands that may be used to repair a broken file.
+    /// Returns what had to be left out.
+    pub fn load_lenient() -> Result<(Self, Vec<String>), AppError> {
+        let path = match config_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok((Self::default(), Vec::new())),
+        };
+        let text = fs::read_to_string(&path)
+            .map_err(|e| AppError::Storage(format!("Failed to read {}: {}", path.display(), e)))?;
+        Ok(Self::parse_lenient(&text, &path))
+    }
+
+    // The part of `load_lenient` after reading `path`
+    fn parse_lenient(text: &str, path: &Path) -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let table = match toml::from_str::<toml::Table>(text) {
+            Ok(table) => table,
+            Err(e) => {
+                let skipped = format!("everything in {}: {}", path.display(), e.message());
+                return (config, vec![skipped]);
+            }
+        };
+
+        let mut entries = Vec::new();
+        flatten_values("", &table, &mut entries);
+        let mut skipped = Vec::new();
+        for (key, value) in entries {
+            let shown = format!("{} = {}", key, value);
+            if let Err(e) = config.set_value(&key, value) {
+                skipped.push(format!("{} ({})", shown, e));
+            }
+        }
+        (config, skipped)
+    }
+
+    /// Copy the config file to `config.toml.bak`, returning where it went
+    pub fn backup() -> Result<Option<PathBuf>, AppError> {
+        let Some(path) = config_path().filter(|p| p.exists()) else {
+            return Ok(None);
+        };
+        let backup = path.with_extension("toml.bak");
+        fs::copy(&path, &backup).map_err(|e| {
+            AppError::Storage(format!("Failed to write {}: {}", backup.display(), e))
+        })?;
+        Ok(Some(backup))
+    }
+
+    pub fn save(&self) -> Result<(), AppError> {
+        let path = config_path().ok_or_else(|| {
+            AppError::Storage("Cannot locate the config directory: HOME is not set".to_string())
+        })?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| {
+                AppError::Storage(format!("Failed to create {}: {}", dir.display(), e))
+            })?;
+        }
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| AppError::Storage(format!("Failed to serialize config: {}", e)))?;
+        fs::write(&path, text)
+            .map_err(|e| AppError::Storage(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    // Range checks serde can't express
+    fn validate(&self) -> Result<(), AppError> {
+        if self
+            .data_dir
+            .as_deref()
+            .is_some_and(|d| d.trim().is_empty())
+        {
+            return Err(invalid("data_dir", "must not be empty"));
+        }
+        let positive = [
+            ("pomodoro.work", self.pomodoro.work),
+            ("pomodoro.short_break", self.pomodoro.short_break),
+            ("pomodoro.long_break", self.pomodoro.long_break),
+            ("pomodoro.sessions", self.pomodoro.sessions),
+            (
+                "pomodoro.long_break_interval",
+                self.pomodoro.long_break_interval,
+            ),
+        ];
+        for (key, value) in positive {
+            if value == 0 {
+                return Err(invalid(key, "must be at least 1"));
+            }
+        }
+        // `truncate` needs room for the "..." suffix
+        for (key, value) in [
+            ("output.title_width", self.output.title_width),
+            ("output.description_width", self.output.description_width),
+        ] {
+            if value < 4 {
+                return Err(invalid(key, "must be at least 4"));
+            }
+        }
+        let u = &self.urgency;
+        for (key, value) in [
+            ("urgency.due", u.due),
+            ("urgency.priority_high", u.priority_high),
+            ("urgency.priority_medium", u.priority_medium),
+            ("urgency.priority_low", u.priority_low),
+            ("urgency.age", u.age),
+            ("urgency.blocking", u.blocking),
+            ("urgency.tags", u.tags),
+        ] {
+            if !value.is_finite() {
+                return Err(invalid(key, "must be a finite number"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every setting as `(dotted.key, value)`, unset optional keys included
+    pub fn entries(&self) -> Result<Vec<(String, String)>, AppError> {
+        let mut entries = Vec::new();
+        flatten("", &self.to_table()?, &mut entries);
+        if self.data_dir.is_none() {
+            entries.insert(0, ("data_dir".to_string(), String::new()));
+        }
+        Ok(entries)
+    }
+
+    pub fn get(&self, key: &str) -> Result<String, AppError> {
+        self.entries()?
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .ok_or_else(|| unknown_key(key))
+    }
+
+    /// Set a dotted key from its textual value, rejecting unknown keys and
+    /// values of the wrong type or range
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), AppError> {
+        // Accept anything TOML understands (numbers, booleans), else a string
+        let parsed = toml::from_str::<toml::Table>(&format!("v = {}", value))
+            .ok()
+            .and_then(|mut t| t.remove("v"))
+            .unwrap_or_else(|| toml::Value::String(value.to_string()));
+        self.set_value(key, parsed)
+    }
+
+    fn set_value(&mut self, key: &str, parsed: toml::Value) -> Result<(), AppError> {
+        if !self.entries()?.iter().any(|(k, _)| k == key) {
+            return Err(unknown_key(key));
+        }
+
+        let mut table = self.to_table()?;
+        let mut parts: Vec<&str> = key.split('.').collect();
+        let leaf = parts.pop().unwrap_or(key);
+        let mut section = &mut table;
+        for part in parts {
+            section = section
+                .get_mut(part)
+                .and_then(toml::Value::as_table_mut)
+                .ok_or_else(|| unknown_key(key))?;
+        }
+        section.insert(leaf.to_string(), parsed);
+
+        let updated: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| invalid(key, e.message()))?;
+        updated.validate()?;
+        *self = updated;
+        Ok(())
+    }
+
+    fn to_table(&self) -> Result<toml::Table, AppError> {
+        toml::Table::try_from(self)
+            .map_err(|e| AppError::Storage(format!("Failed to serialize config: {}", e)))
+    }
+}
+
+fn flatten(prefix: &str, table: &toml::Table, out: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let full = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(inner) => flatten(&full, inner, out),
+            toml::Value::String(s) => out.push((full, s.clone())),
+            other => out.push((full, other.to_string())),
+        }
+    }
+}
+
+// Like `flatten`, keeping the values as they are
+fn flatten_values(prefix: &str, table: &toml::Table, out: &mut Vec<(String, toml::Value)>) {
+    for (key, value) in table {
+        let full = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(inner) => flatten_values(&full, inner, out),
+            other => out.push((full, other.clone())),
+        }
+    }
+}
+
+fn unknown_key(key: &str) -> AppError {
+    AppError::Validation(format!("Unknown config key: {}", key))
+}
+
+fn invalid(key: &str, reason: &str) -> AppError {
+    AppError::Validation(format!("Invalid value for {}: {}", key, reason))
+}
+
+/// Location of the config file, following the XDG base directory spec
+pub fn config_path() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("rtask").join("config.toml"))
+}
+
+/// Where the data lives and how that was decided
+pub struct DataDir {
+    pub path: String,
+    /// True when nothing was configured and the XDG default is used
+    pub is_default: bool,
+}
+
+/// Resolve the data directory: `--data-dir`, then `RTASK_DATA_DIR`, then the
+/// config file, then `$XDG_DATA_HOME/rtask`. Empty values count as unset.
+pub fn resolve_data_dir(flag: Option<String>, config: &Config) -> DataDir {
+    let given = |v: &String| !v.trim().is_empty();
+    let explicit = flag
+        .filter(given)
+        .or_else(|| env::var(DATA_DIR_ENV).ok().filter(given))
+        .or_else(|| config.data_dir.clone().filter(given));
+    if let Some(path) = explicit {
+        return DataDir {
+            path,
+            is_default: false,
+        };
+    }
+
+    match xdg_dir("XDG_DATA_HOME", ".local/share") {
+        Some(dir) => DataDir {
+            path: dir.join("rtask").to_string_lossy().into_owned(),
+            is_default: true,
+        },
+        // No home directory to speak of: keep the old behaviour
+        None => DataDir {
+            path: LEGACY_DATA_DIR.to_string(),
+            is_default: false,
+        },
+    }
+}
+
+// `$VAR` if set to an absolute path, otherwise `$HOME/<fallback>`
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| {
+            env::var_os("HOME")
+                .filter(|h| !h.is_empty())
+                .map(|h| Path::new(&h).join(fallback))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_empty_data_dir_is_rejected() {
+        let mut config = Config::default();
+        for value in ["\"\"", "\"  \""] {
+            let result = config.set("data_dir", value);
+            assert!(matches!(result, Err(AppError::Validation(_))), "{}", value);
+        }
+        assert!(config.data_dir.is_none());
+        assert!(
+            toml::from_str::<Config>("data_dir = \"\"")
+                .unwrap()
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn an_empty_data_dir_flag_counts_as_unset() {
+        let config = Config {
+            data_dir: Some("/srv/rtask".to_string()),
+            ..Config::default()
+        };
+        let resolved = resolve_data_dir(Some(String::new()), &config);
+        assert_ne!(resolved.path, "");
+        let config = Config {
+            data_dir: Some(String::new()),
+            ..Config::default()
+        };
+        assert_ne!(
+            resolve_data_dir(Some(" ".to_string()), &config).path.trim(),
+            ""
+        );
+    }
+
+    #[test]
+    fn set_parses_toml_values_and_falls_back_to_strings() {
+        let mut config = Config::default();
+        config.set("pomodoro.work", "50").unwrap();
+        assert_eq!(config.pomodoro.work, 50);
+        // `3/week` isn't TOML, so it is taken as a string
+        config.set("habit.frequency", "3/week").unwrap();
+        assert_eq!(config.habit.frequency, Schedule::PerWeek(3));
+        config.set("storage.backend", "sqlite").unwrap();
+        assert_eq!(config.storage.backend, StorageBackend::Sqlite);
+        config.set("urgency.due", "-2.5").unwrap();
+        assert_eq!(config.urgency.due, -2.5);
+        assert_eq!(config.get("pomodoro.work").unwrap(), "50");
+        assert_eq!(config.get("data_dir").unwrap(), "");
+    }
+
+    #[test]
+    fn set_leaves_the_config_alone_on_bad_input() {
+        let mut config = Config::default();
+        for (key, value) in [
+            ("pomodoro.work", "0"),
+            ("pomodoro.work", "lots"),
+            ("output.title_width", "3"),
+            ("urgency.due", "nan"),
+            ("habit.frequency", "every 0 days"),
+            ("storage.backend", "csv"),
+            ("pomodoro", "25"),
+            ("colour", "red"),
+        ] {
+            let result = config.set(key, value);
+            assert!(matches!(result, Err(AppError::Validation(_))), "{}", key);
+        }
+        assert_eq!(config.pomodoro.work, 25);
+        assert_eq!(config.output.title_width, 20);
+    }
+
+    #[test]
+    fn lenient_loading_keeps_the_valid_settings() {
+        let text = "
+            [pomodoro]
+            work = 50
+            short_break = 0
+
+            [output]
+            colour = true
+            title_width = 30
+        ";
+        assert!(toml::from_str::<Config>(text).is_err());
+        let (config, skipped) = Config::parse_lenient(text, Path::new("config.toml"));
+        assert_eq!(config.pomodoro.work, 50);
+        assert_eq!(config.pomodoro.short_break, 5);
+        assert_eq!(config.output.title_width, 30);
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped[0].starts_with("output.colour = true"));
+        assert!(skipped[1].starts_with("pomodoro.short_break = 0"));
+    }
+
+    #[test]
+    fn lenient_loading_survives_broken_toml() {
+        let (config, skipped) = Config::parse_lenient("[pomodoro", Path::new("config.toml"));
+        assert_eq!(config.pomodoro.work, 25);
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].starts_with("everything in config.toml"));
+    }
+
+    #[test]
+    fn validate_checks_ranges_serde_cannot() {
+        assert!(Config::default().validate().is_ok());
+        let mut config = Config::default();
+        config.pomodoro.long_break_interval = 0;
+        assert!(config.validate().is_err());
+        let mut config = Config::default();
+        config.output.description_width = 3;
+        assert!(config.validate().is_err());
+        let mut config = Config::default();
+        config.urgency.tags = f64::INFINITY;
+        assert!(config.validate().is_err());
+    }
+}
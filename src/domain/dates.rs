@@ -0,0 +1,274 @@
+use chrono::{
+    DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    TimeZone, Weekday,
+};
+
+/// Parse a human date expression relative to `now`.
+///
+/// Understands, case-insensitively:
+/// - `now`, `today`, `tomorrow`, `yesterday`
+/// - weekdays: `fri` (today or the coming one), `next monday`, `last fri`
+/// - offsets: `in 3 days`, `in 2 weeks`, `in 90 minutes`, `3 days ago`,
+///   `next week`, `next month`
+/// - period ends: `eod`, `eow`, `eom`, `eoy`
+/// - absolute dates: `2026-11-02`, `2026-11-02 14:00`, RFC 3339
+///
+/// Any day-level expression can be followed by a time (`9am`, `9:30pm`,
+/// `14:00`, `noon`, `midnight`, optionally after `at`); without one it
+/// resolves to the start of that day.
+pub fn parse_date(input: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let input = input.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(dt.with_timezone(&Local));
+    }
+
+    let lower = input.to_lowercase();
+    let mut words: Vec<&str> = lower.split_whitespace().filter(|w| *w != "at").collect();
+    if words.is_empty() {
+        return Err("Empty date".to_string());
+    }
+
+    // A trailing time of day applies to whatever day the rest describes
+    let time = match words.last().and_then(|w| parse_time(w)) {
+        Some(t) if words.len() > 1 => {
+            words.pop();
+            Some(t)
+        }
+        // A lone time means today at that time
+        Some(t) => return to_local(now.date_naive().and_time(t), input),
+        None => None,
+    };
+
+    match parse_expr(&words, now) {
+        Some(Parsed::Day(day)) => to_local(day.and_time(time.unwrap_or(NaiveTime::MIN)), input),
+        Some(Parsed::At(at)) if time.is_none() => Ok(at),
+        Some(Parsed::At(_)) => Err(format!("'{}' already includes a time", input)),
+        None => Err(format!("Could not understand the date '{}'", input)),
+    }
+}
+
+/// What a date expression resolved to before any time of day is applied
+enum Parsed {
+    Day(NaiveDate),
+    At(DateTime<Local>),
+}
+
+fn parse_expr(words: &[&str], now: DateTime<Local>) -> Option<Parsed> {
+    let today = now.date_naive();
+    let end_of = |day: NaiveDate| {
+        Local
+            .from_local_datetime(&day.and_hms_opt(23, 59, 59)?)
+            .earliest()
+            .map(Parsed::At)
+    };
+
+    match words {
+        ["now"] => Some(Parsed::At(now)),
+        ["today"] => Some(Parsed::Day(today)),
+        ["tomorrow"] => Some(Parsed::Day(today + Duration::days(1))),
+        ["yesterday"] => Some(Parsed::Day(today - Duration::days(1))),
+        ["eod"] => end_of(today),
+        ["eow"] => {
+            end_of(today + Duration::days(6 - today.weekday().num_days_from_monday() as i64))
+        }
+        ["eom"] => end_of(last_day_of_month(today)?),
+        ["eoy"] => end_of(NaiveDate::from_ymd_opt(today.year(), 12, 31)?),
+        ["next", "week"] => Some(Parsed::Day(today + Duration::days(7))),
+        ["next", "month"] => Some(Parsed::Day(today.checked_add_months(Months::new(1))?)),
+        ["next", "year"] => Some(Parsed::Day(today.checked_add_months(Months::new(12))?)),
+        ["next", day] => {
+            let weekday = parse_weekday(day)?;
+            Some(Parsed::Day(upcoming(today + Duration::days(1), weekday)))
+        }
+        ["last", day] => {
+            let weekday = parse_weekday(day)?;
+            let back =
+                (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday() - 1)
+                    % 7
+                    + 1;
+            Some(Parsed::Day(today - Duration::days(back as i64)))
+        }
+        ["in", amount, unit] => offset(now, amount.parse().ok()?, unit),
+        [amount, unit, "ago"] => offset(now, amount.parse::<i64>().ok()?.checked_neg()?, unit),
+        [word] => {
+            if let Some(weekday) = parse_weekday(word) {
+                return Some(Parsed::Day(upcoming(today, weekday)));
+            }
+            if let Ok(day) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+                return Some(Parsed::Day(day));
+            }
+            // The input was lowercased, hence the `t` separator
+            let naive = NaiveDateTime::parse_from_str(word, "%Y-%m-%dt%H:%M")
+                .or_else(|_| NaiveDateTime::parse_from_str(word, "%Y-%m-%dt%H:%M:%S"))
+                .ok()?;
+            Local.from_local_datetime(&naive).earliest().map(Parsed::At)
+        }
+        _ => None,
+    }
+}
+
+// `in N <unit>` / `N <unit> ago`; units of a day or more keep day precision.
+// Amounts beyond the representable range give `None` rather than a panic.
+fn offset(now: DateTime<Local>, amount: i64, unit: &str) -> Option<Parsed> {
+    let today = now.date_naive();
+    let unit = unit.trim_end_matches('s');
+    let months = |n: i64| {
+        let count = Months::new(u32::try_from(n.unsigned_abs()).ok()?);
+        if n >= 0 {
+            today.checked_add_months(count)
+        } else {
+            today.checked_sub_months(count)
+        }
+    };
+    let at = |delta: Option<TimeDelta>| now.checked_add_signed(delta?).map(Parsed::At);
+    let day = |delta: Option<TimeDelta>| today.checked_add_signed(delta?).map(Parsed::Day);
+    match unit {
+        "min" | "minute" => at(TimeDelta::try_minutes(amount)),
+        "h" | "hr" | "hour" => at(TimeDelta::try_hours(amount)),
+        "d" | "day" => day(TimeDelta::try_days(amount)),
+        "w" | "wk" | "week" => day(TimeDelta::try_weeks(amount)),
+        "month" => months(amount).map(Parsed::Day),
+        "y" | "yr" | "year" => months(amount.checked_mul(12)?).map(Parsed::Day),
+        _ => None,
+    }
+}
+
+/// Parse a weekday name or its three-letter abbreviation
+pub fn parse_weekday(word: &str) -> Option<Weekday> {
+    let weekday = match word.to_lowercase().as_str() {
+        "mon" | "monday" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" => Weekday::Tue,
+        "wed" | "wednesday" => Weekday::Wed,
+        "thu" | "thur" | "thurs" | "thursday" => Weekday::Thu,
+        "fri" | "friday" => Weekday::Fri,
+        "sat" | "saturday" => Weekday::Sat,
+        "sun" | "sunday" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(weekday)
+}
+
+// First day on or after `from` that falls on `weekday`
+fn upcoming(from: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (7 + weekday.num_days_from_monday() - from.weekday().num_days_from_monday()) % 7;
+    from + Duration::days(ahead as i64)
+}
+
+fn last_day_of_month(day: NaiveDate) -> Option<NaiveDate> {
+    day.with_day(1)?
+        .checked_add_months(Months::new(1))?
+        .pred_opt()
+}
+
+// `9am`, `9:30pm`, `14:00`, `noon`, `midnight`
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    match word {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return Some(NaiveTime::MIN),
+        _ => {}
+    }
+
+    let (clock, offset) = if let Some(rest) = word.strip_suffix("am") {
+        (rest, Some(0))
+    } else if let Some(rest) = word.strip_suffix("pm") {
+        (rest, Some(12))
+    } else {
+        (word, None)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        // A bare number is only a time with am/pm, otherwise it's ambiguous
+        None if offset.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+
+    let hour = match offset {
+        Some(add) if (1..=12).contains(&hour) => hour % 12 + add,
+        Some(_) => return None,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn to_local(naive: NaiveDateTime, input: &str) -> Result<DateTime<Local>, String> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("'{}' does not exist in the local time zone", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wednesday 2026-10-14, 10:00
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 14, 10, 0, 0).unwrap()
+    }
+
+    fn parse(input: &str) -> String {
+        parse_date(input, now())
+            .unwrap()
+            .naive_local()
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    }
+
+    #[test]
+    fn relative_days_start_at_midnight() {
+        assert_eq!(parse("today"), "2026-10-14 00:00:00");
+        assert_eq!(parse("Tomorrow"), "2026-10-15 00:00:00");
+        assert_eq!(parse("yesterday"), "2026-10-13 00:00:00");
+        assert_eq!(parse("in 3 days"), "2026-10-17 00:00:00");
+        assert_eq!(parse("2 weeks ago"), "2026-09-30 00:00:00");
+        assert_eq!(parse("next month"), "2026-11-14 00:00:00");
+        assert_eq!(parse("in 1 year"), "2027-10-14 00:00:00");
+    }
+
+    #[test]
+    fn weekdays_look_ahead_and_back() {
+        assert_eq!(parse("wed"), "2026-10-14 00:00:00");
+        assert_eq!(parse("friday"), "2026-10-16 00:00:00");
+        assert_eq!(parse("next wed"), "2026-10-21 00:00:00");
+        assert_eq!(parse("last wed"), "2026-10-07 00:00:00");
+        assert_eq!(parse("last fri"), "2026-10-09 00:00:00");
+    }
+
+    #[test]
+    fn times_apply_to_the_day() {
+        assert_eq!(parse("now"), "2026-10-14 10:00:00");
+        assert_eq!(parse("9:30pm"), "2026-10-14 21:30:00");
+        assert_eq!(parse("tomorrow at 9am"), "2026-10-15 09:00:00");
+        assert_eq!(parse("fri noon"), "2026-10-16 12:00:00");
+        assert_eq!(parse("12am"), "2026-10-14 00:00:00");
+        assert_eq!(parse("in 90 minutes"), "2026-10-14 11:30:00");
+        assert_eq!(parse("2026-11-02 14:00"), "2026-11-02 14:00:00");
+        assert_eq!(parse("2026-11-02T14:00"), "2026-11-02 14:00:00");
+    }
+
+    #[test]
+    fn period_ends_are_the_last_second() {
+        assert_eq!(parse("eod"), "2026-10-14 23:59:59");
+        assert_eq!(parse("eow"), "2026-10-18 23:59:59");
+        assert_eq!(parse("eom"), "2026-10-31 23:59:59");
+        assert_eq!(parse("eoy"), "2026-12-31 23:59:59");
+    }
+
+    #[test]
+    fn rejects_nonsense_and_out_of_range_offsets() {
+        for input in [
+            "",
+            "someday",
+            "13pm",
+            "now 9am",
+            "in 99999999999 days",
+            "in 9223372036854775807 minutes",
+            "in 5000000000 months",
+            "in 999999999999 years",
+            "-9223372036854775808 days ago",
+        ] {
+            assert!(parse_date(input, now()).is_err(), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use crate::domain::dates;
+use crate::domain::models::{Priority, Status, Task, parse_project, parse_tag};
+use chrono::{DateTime, Local};
+
+/// A task query such as `status:pending project:work due.before:fri +urgent`.
+///
+/// Every term must match. Terms are:
+/// - `status:todo|in-progress|waiting|done|cancelled`, or `status:pending`
+///   (any open state), `status:closed`, `status:all`
+/// - `project:work` (subprojects included), `project:` for no project
+/// - `priority:H`, `priority:none`
+/// - `due.before:<date>`, `due.after:<date>`, `due:<date>` (same day)
+/// - `id:3` or `id:3,5,8`
+/// - `+tag`, `-tag`, `tag:name`
+/// - anything else, including words with a colon whose key isn't one of the
+///   above (`re: budget`, `http://...`, `9:30`), is searched for in the
+///   title, description and annotations
+#[derive(Debug, Default)]
+pub struct Filter {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug)]
+enum Term {
+    Status(Vec<Status>),
+    Project(Option<String>),
+    Priority(Option<Priority>),
+    DueBefore(DateTime<Local>),
+    DueAfter(DateTime<Local>),
+    DueOn(DateTime<Local>),
+    Ids(Vec<u32>),
+    Tag(String),
+    NoTag(String),
+    Text(String),
+}
+
+impl Filter {
+    /// Parse one term per word, resolving dates relative to `now`
+    pub fn parse<S: AsRef<str>>(words: &[S], now: DateTime<Local>) -> Result<Self, String> {
+        let mut filter = Filter::default();
+        for word in words {
+            filter.terms.push(parse_term(word.as_ref(), now)?);
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        self.terms.iter().all(|term| term.matches(task))
+    }
+}
+
+fn parse_term(word: &str, now: DateTime<Local>) -> Result<Term, String> {
+    let word = word.trim();
+    if let Some(tag) = word.strip_prefix('+') {
+        return parse_tag(tag).map(Term::Tag);
+    }
+    // A lone `-` or a negative number is text, not an exclusion
+    if let Some(tag) = word.strip_prefix('-')
+        && tag.starts_with(|c: char| c.is_alphabetic())
+    {
+        return parse_tag(tag).map(Term::NoTag);
+    }
+
+    // Only known keys make a term, so "re: budget" or a URL is still text
+    let Some((key, value)) = word
+        .split_once(':')
+        .filter(|(key, _)| KEYS.contains(&key.to_lowercase().as_str()))
+    else {
+        return Ok(Term::Text(word.to_lowercase()));
+    };
+    let date = |value: &str| dates::parse_date(value, now);
+    match key.to_lowercase().as_str() {
+        "status" => {
+            let all = Status::ALL.into_iter();
+            match value.to_lowercase().as_str() {
+                "pending" | "open" => Ok(Term::Status(all.filter(|s| s.is_open()).collect())),
+                "closed" => Ok(Term::Status(all.filter(|s| !s.is_open()).collect())),
+                "all" | "any" => Ok(Term::Status(all.collect())),
+                _ => value.parse().map(|s| Term::Status(vec![s])),
+            }
+        }
+        "project" | "proj" if value.is_empty() => Ok(Term::Project(None)),
+        "project" | "proj" => parse_project(value).map(|p| Term::Project(Some(p))),
+        "priority" | "pri" if value.eq_ignore_ascii_case("none") || value.is_empty() => {
+            Ok(Term::Priority(None))
+        }
+        "priority" | "pri" => value.parse().map(|p| Term::Priority(Some(p))),
+        "due.before" | "due.by" => date(value).map(Term::DueBefore),
+        "due.after" => date(value).map(Term::DueAfter),
+        "due" => date(value).map(Term::DueOn),
+        "id" => value
+            .split(',')
+            .map(|id| {
+                id.trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid task ID '{}'", id))
+            })
+            .collect::<Result<_, _>>()
+            .map(Term::Ids),
+        "tag" => parse_tag(value).map(Term::Tag),
+        _ => Ok(Term::Text(word.to_lowercase())),
+    }
+}
+
+const KEYS: &[&str] = &[
+    "status",
+    "project",
+    "proj",
+    "priority",
+    "pri",
+    "due.before",
+    "due.by",
+    "due.after",
+    "due",
+    "id",
+    "tag",
+];
+
+impl Term {
+    fn matches(&self, task: &Task) -> bool {
+        match self {
+            Term::Status(statuses) => statuses.contains(&task.status),
+            Term::Project(Some(project)) => task.in_project(project),
+            Term::Project(None) => task.project.is_none(),
+            Term::Priority(priority) => task.priority == *priority,
+            Term::DueBefore(at) => task.due.is_some_and(|due| due < *at),
+            Term::DueAfter(at) => task.due.is_some_and(|due| due > *at),
+            Term::DueOn(at) => task
+                .due
+                .is_some_and(|due| due.date_naive() == at.date_naive()),
+            Term::Ids(ids) => ids.contains(&task.id),
+            Term::Tag(tag) => task.has_tag(tag),
+            Term::NoTag(tag) => !task.has_tag(tag),
+            Term::Text(text) => {
+                task.title.to_lowercase().contains(text)
+                    || task
+                        .description
+                        .as_deref()
+                        .is_some_and(|d| d.to_lowercase().contains(text))
+                    || task
+                        .annotations
+                        .iter()
+                        .any(|a| a.text.to_lowercase().contains(text))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    // Wednesday 2026-10-14, 10:00
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 14, 10, 0, 0).unwrap()
+    }
+
+    fn task() -> Task {
+        let mut task: Task = serde_json::from_value(json!({
+            "id": 3,
+            "title": "Re: budget review",
+            "description": "see http://example.com at 9:30",
+            "created_at": "2026-10-01T08:00:00Z",
+            "status": "in-progress",
+            "priority": "H",
+            "tags": ["urgent"],
+            "project": "work.backend"
+        }))
+        .unwrap();
+        task.due = Some(Local.with_ymd_and_hms(2026, 10, 16, 17, 0, 0).unwrap());
+        task
+    }
+
+    fn matches(words: &[&str]) -> bool {
+        Filter::parse(words, now()).unwrap().matches(&task())
+    }
+
+    #[test]
+    fn every_term_must_match() {
+        assert!(matches(&[
+            "status:pending",
+            "project:work",
+            "+urgent",
+            "pri:h"
+        ]));
+        assert!(!matches(&["status:pending", "-urgent"]));
+        assert!(matches(&[]));
+    }
+
+    #[test]
+    fn keys_match_their_field() {
+        assert!(matches(&["status:in-progress"]));
+        assert!(!matches(&["status:closed"]));
+        assert!(matches(&["project:work.backend"]));
+        assert!(!matches(&["project:"]));
+        assert!(!matches(&["priority:none"]));
+        assert!(matches(&["id:1,3"]));
+        assert!(matches(&["tag:urgent"]));
+    }
+
+    #[test]
+    fn due_terms_use_dates_relative_to_now() {
+        assert!(matches(&["due:fri"]));
+        assert!(matches(&["due.before:sat", "due.after:tomorrow"]));
+        assert!(!matches(&["due.before:fri"]));
+    }
+
+    #[test]
+    fn unknown_keys_are_searched_as_text() {
+        assert!(matches(&["re:"]));
+        assert!(matches(&["http://example.com"]));
+        assert!(matches(&["9:30"]));
+        assert!(matches(&["BUDGET"]));
+        assert!(!matches(&["note:missing"]));
+        // A negative number isn't a tag exclusion
+        assert!(!matches(&["-5"]));
+    }
+
+    #[test]
+    fn rejects_bad_values_for_known_keys() {
+        for word in ["status:later", "priority:X", "id:x", "due:someday"] {
+            assert!(Filter::parse(&[word], now()).is_err(), "{}", word);
+        }
+    }
+}
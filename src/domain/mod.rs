@@ -1,2 +1,6 @@
+pub mod dates;
+pub mod filter;
 pub mod models;
-pub mod services;
\ No newline at end of file
+pub mod services;
+pub mod stats;
+pub mod urgency;
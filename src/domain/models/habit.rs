@@ -1,13 +1,385 @@
-use chrono::{DateTime, Local};
+use crate::domain::models::{Period, Schedule};
+use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Habit {
     pub id: u32,
     pub name: String,
     pub description: Option<String>,
-    pub frequency: String, // e.g., "daily", "weekly"
+    pub schedule: Schedule,
+    /// Current streak as of the last change to `log`
     pub streak: u32,
     pub created_at: DateTime<Local>,
-    pub last_completed_at: Option<DateTime<Local>>,
+    /// Check-ins, oldest first, one per day
+    #[serde(default)]
+    pub log: Vec<CheckIn>,
+    /// Amount to reach in each period, for habits like "read 20 pages"
+    #[serde(default)]
+    pub measure: Option<Measure>,
+}
+
+/// The target amount of a measurable habit
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Measure {
+    pub target: f64,
+    /// What is counted, e.g. `pages`; may be empty
+    #[serde(default)]
+    pub unit: String,
+}
+
+/// Where a habit stands in its current period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HabitState {
+    /// The period has all its check-ins
+    Done,
+    /// Check-ins are still needed and there are days left for them
+    Due,
+    /// Not enough days are left in the period to complete it. Never the
+    /// case in the period the habit was created in.
+    Missed,
+    /// Not a scheduled day
+    Off,
+}
+
+impl fmt::Display for HabitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            HabitState::Done => "done",
+            HabitState::Due => "due",
+            HabitState::Missed => "missed",
+            HabitState::Off => "off",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A day the habit was done on
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CheckIn {
+    pub date: NaiveDate,
+    /// When the check-in was recorded
+    pub at: DateTime<Local>,
+    /// Total amount done that day, for measurable habits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<f64>,
+}
+
+impl Measure {
+    /// `12 pages`
+    pub fn format(&self, amount: f64) -> String {
+        self.with_unit(format_amount(amount))
+    }
+
+    /// `12/20 pages`
+    pub fn progress(&self, amount: f64) -> String {
+        self.with_unit(format!(
+            "{}/{}",
+            format_amount(amount),
+            format_amount(self.target)
+        ))
+    }
+
+    fn with_unit(&self, amount: String) -> String {
+        if self.unit.is_empty() {
+            amount
+        } else {
+            format!("{} {}", amount, self.unit)
+        }
+    }
+}
+
+impl fmt::Display for Measure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(self.target))
+    }
+}
+
+// At most two decimals, without trailing zeros: `12`, `2.5`, `0.33`
+fn format_amount(amount: f64) -> String {
+    let s = format!("{:.2}", amount);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+impl Habit {
+    // `EveryDays` periods are counted from the day the habit was created
+    fn anchor(&self) -> NaiveDate {
+        self.created_at.date_naive()
+    }
+
+    /// The period `day` falls in, or the last one before it
+    pub fn period(&self, day: NaiveDate) -> Period {
+        self.schedule.latest(day, self.anchor())
+    }
+
+    /// Days with a check-in within `period`
+    pub fn done_days(&self, period: Period) -> u32 {
+        self.log.iter().filter(|c| period.contains(c.date)).count() as u32
+    }
+
+    /// Sum of the amounts checked in within `period`
+    pub fn amount(&self, period: Period) -> f64 {
+        self.log
+            .iter()
+            .filter(|c| period.contains(c.date))
+            .filter_map(|c| c.amount)
+            .fold(0.0, |sum, amount| sum + amount)
+    }
+
+    /// True when `period` has all the check-ins it needs, or for measurable
+    /// habits when its amounts add up to the target
+    pub fn is_complete(&self, period: Period) -> bool {
+        match &self.measure {
+            Some(measure) => self.amount(period) >= measure.target,
+            None => self.done_days(period) >= self.schedule.target(),
+        }
+    }
+
+    /// True when the period `day` falls in is complete
+    pub fn is_done_in(&self, day: NaiveDate) -> bool {
+        self.is_complete(self.period(day))
+    }
+
+    /// True when there is a check-in on `day` itself
+    pub fn is_checked_on(&self, day: NaiveDate) -> bool {
+        self.log.iter().any(|c| c.date == day)
+    }
+
+    /// Date of the latest check-in
+    pub fn last_check_in(&self) -> Option<NaiveDate> {
+        self.log.iter().map(|c| c.date).max()
+    }
+
+    /// How the period containing `today` is going
+    pub fn state(&self, today: NaiveDate) -> HabitState {
+        if !self.schedule.is_scheduled(today) {
+            return HabitState::Off;
+        }
+        let period = self.period(today);
+        // Any amount can still be added until the period is over
+        if self.measure.is_some() {
+            return if self.is_complete(period) {
+                HabitState::Done
+            } else {
+                HabitState::Due
+            };
+        }
+        let needed = self
+            .schedule
+            .target()
+            .saturating_sub(self.done_days(period));
+        if needed == 0 {
+            return HabitState::Done;
+        }
+        // Days from today to the end of the period still free for a check-in
+        let mut left = (period.end - today).num_days() as u32;
+        if !self.is_checked_on(today) {
+            left += 1;
+        }
+        // The period the habit was created in may be too short to complete,
+        // and doesn't count against it
+        if left >= needed || period.contains(self.anchor()) {
+            HabitState::Due
+        } else {
+            HabitState::Missed
+        }
+    }
+
+    /// Every period from the one the habit was created in up to the one
+    /// containing `today`, oldest first, with whether it was completed
+    fn history(&self, today: NaiveDate) -> Vec<(Period, bool)> {
+        let mut periods = Vec::new();
+        let mut period = self.period(today);
+        while period.end >= self.anchor() {
+            periods.push((period, self.is_complete(period)));
+            period = self.schedule.previous(period, self.anchor());
+        }
+        periods.reverse();
+        periods
+    }
+
+    /// Completed periods in a row up to the one containing `today`. The
+    /// current period doesn't break the streak while it's still running.
+    pub fn current_streak(&self, today: NaiveDate) -> u32 {
+        let mut history = self.history(today);
+        if history
+            .last()
+            .is_some_and(|(p, done)| p.contains(today) && !done)
+        {
+            history.pop();
+        }
+        history.iter().rev().take_while(|(_, done)| *done).count() as u32
+    }
+
+    /// The longest run of completed periods
+    pub fn longest_streak(&self, today: NaiveDate) -> u32 {
+        let (mut longest, mut run) = (0, 0);
+        for (_, done) in self.history(today) {
+            run = if done { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+        longest
+    }
+
+    /// Share of periods since the habit was created that were completed.
+    /// The first and the current period only count once completed, as they
+    /// may be cut short. `None` until a period counts.
+    pub fn completion_rate(&self, today: NaiveDate) -> Option<f64> {
+        let history = self.history(today);
+        let last = history.len().saturating_sub(1);
+        let counted: Vec<bool> = history
+            .iter()
+            .enumerate()
+            .filter(|(i, (_, done))| *done || (*i != 0 && *i != last))
+            .map(|(_, (_, done))| *done)
+            .collect();
+        let done = counted.iter().filter(|d| **d).count();
+        (!counted.is_empty()).then(|| done as f64 / counted.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn habit(schedule: &str, created: &str, check_ins: &[&str]) -> Habit {
+        let created_at = Local
+            .from_local_datetime(&day(created).and_hms_opt(8, 0, 0).unwrap())
+            .unwrap();
+        Habit {
+            id: 1,
+            name: "read".to_string(),
+            description: None,
+            schedule: schedule.parse().unwrap(),
+            streak: 0,
+            created_at,
+            log: check_ins
+                .iter()
+                .map(|d| CheckIn {
+                    date: day(d),
+                    at: created_at,
+                    amount: None,
+                })
+                .collect(),
+            measure: None,
+        }
+    }
+
+    #[test]
+    fn a_running_day_does_not_break_the_streak() {
+        let checks = [
+            "2026-10-01",
+            "2026-10-02",
+            "2026-10-03",
+            "2026-10-05",
+            "2026-10-06",
+        ];
+        let habit = habit("daily", "2026-10-01", &checks);
+        assert_eq!(habit.current_streak(day("2026-10-07")), 2);
+        assert_eq!(habit.current_streak(day("2026-10-08")), 0);
+        assert_eq!(habit.longest_streak(day("2026-10-08")), 3);
+    }
+
+    #[test]
+    fn weekly_streaks_need_every_check_in_of_the_week() {
+        // Weeks starting 09-28 (done), 10-05 (one of two) and 10-12 (done)
+        let checks = [
+            "2026-09-29",
+            "2026-10-01",
+            "2026-10-06",
+            "2026-10-12",
+            "2026-10-13",
+        ];
+        let habit = habit("2/week", "2026-09-28", &checks);
+        assert_eq!(habit.current_streak(day("2026-10-14")), 1);
+        assert_eq!(habit.longest_streak(day("2026-10-14")), 1);
+    }
+
+    #[test]
+    fn completion_rate_leaves_out_unfinished_edge_periods() {
+        let checks = [
+            "2026-10-01",
+            "2026-10-02",
+            "2026-10-03",
+            "2026-10-05",
+            "2026-10-06",
+        ];
+        let old = habit("daily", "2026-10-01", &checks);
+        // 10-07 is still running, so five of the six days before it count
+        assert_eq!(old.completion_rate(day("2026-10-07")), Some(5.0 / 6.0));
+
+        let new = habit("daily", "2026-10-07", &[]);
+        assert_eq!(new.completion_rate(day("2026-10-07")), None);
+    }
+
+    #[test]
+    fn a_week_without_enough_days_left_is_missed() {
+        // 2026-10-17 is a Saturday, with two days left for three check-ins
+        let saturday = day("2026-10-17");
+        let old = habit("3/week", "2026-10-01", &[]);
+        assert_eq!(old.state(saturday), HabitState::Missed);
+        let one = habit("3/week", "2026-10-01", &["2026-10-12"]);
+        assert_eq!(one.state(saturday), HabitState::Due);
+        let all = habit(
+            "3/week",
+            "2026-10-01",
+            &["2026-10-12", "2026-10-13", "2026-10-14"],
+        );
+        assert_eq!(all.state(saturday), HabitState::Done);
+    }
+
+    #[test]
+    fn the_creation_period_is_never_missed() {
+        let habit = habit("3/week", "2026-10-16", &[]);
+        assert_eq!(habit.state(day("2026-10-17")), HabitState::Due);
+        // The next week is held to the schedule again
+        assert_eq!(habit.state(day("2026-10-25")), HabitState::Missed);
+    }
+
+    #[test]
+    fn days_off_the_schedule_are_off() {
+        let habit = habit("mon,wed,fri", "2026-10-01", &[]);
+        assert_eq!(habit.state(day("2026-10-15")), HabitState::Off);
+        assert_eq!(habit.state(day("2026-10-14")), HabitState::Due);
+    }
+
+    #[test]
+    fn measurable_habits_add_up_their_amounts() {
+        let mut habit = habit("daily", "2026-10-01", &["2026-10-14"]);
+        let measure = Measure {
+            target: 20.0,
+            unit: "pages".to_string(),
+        };
+        habit.measure = Some(measure.clone());
+        habit.log[0].amount = Some(12.5);
+        let today = day("2026-10-14");
+        assert_eq!(habit.state(today), HabitState::Due);
+        assert_eq!(
+            measure.progress(habit.amount(habit.period(today))),
+            "12.5/20 pages"
+        );
+
+        habit.log[0].amount = Some(20.0);
+        assert_eq!(habit.state(today), HabitState::Done);
+        // A missed measurable day is still due until it is over
+        assert_eq!(habit.state(day("2026-10-15")), HabitState::Due);
+    }
+
+    #[test]
+    fn amounts_are_shown_with_at_most_two_decimals() {
+        assert_eq!(format_amount(0.1 + 0.2), "0.3");
+        assert_eq!(format_amount(12.0), "12");
+        assert_eq!(format_amount(1.0 / 3.0), "0.33");
+        let bare = Measure {
+            target: 5.0,
+            unit: String::new(),
+        };
+        assert_eq!(bare.progress(2.0), "2/5");
+    }
 }
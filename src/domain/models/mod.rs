@@ -1,8 +1,12 @@
 pub mod habit;
 pub mod pomodoro;
+pub mod recurrence;
+pub mod schedule;
 pub mod task;
 
 // Re-export for easy access
 pub use habit::*;
 pub use pomodoro::*;
+pub use recurrence::*;
+pub use schedule::*;
 pub use task::*;
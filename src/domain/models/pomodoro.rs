@@ -1,7 +1,7 @@
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PomodoroSession {
     pub id: u32,
     pub work_duration: u32, // in minutes
@@ -16,7 +16,24 @@ pub struct PomodoroSession {
     pub completed_sessions: Vec<CompletedSession>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl PomodoroSession {
+    /// True when the break after the current work session is a long one.
+    /// Sessions saved with a zero interval never get one.
+    pub fn is_long_break(&self) -> bool {
+        self.current_session.checked_rem(self.sessions_before_long_break) == Some(0)
+    }
+
+    /// Minutes of the break after the current work session
+    pub fn break_minutes(&self) -> u32 {
+        if self.is_long_break() {
+            self.long_break
+        } else {
+            self.short_break
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CompletedSession {
     pub session_number: u32,
     pub start_time: DateTime<Utc>,
@@ -0,0 +1,389 @@
+use crate::domain::dates::parse_weekday;
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How often a recurring task repeats, modelled on a small subset of
+/// iCalendar's RRULE and stored in that form (`FREQ=WEEKLY;BYDAY=MO,FR`).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(into = "String", try_from = "String")]
+pub struct Recurrence {
+    pub freq: Freq,
+    /// Repeat every `interval` units of `freq`
+    pub interval: u32,
+    /// Specific weekdays, only used with `Freq::Weekly`. With an interval,
+    /// the rest of the current week comes first, then every `interval`-th
+    /// week from there.
+    pub by_day: Vec<Weekday>,
+    /// Day of the month, only used with `Freq::Monthly`. Shorter months use
+    /// their last day, so the series doesn't drift after one of them.
+    pub by_month_day: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Recurrence {
+    /// Largest `interval` accepted when parsing
+    pub const MAX_INTERVAL: u32 = 1000;
+
+    fn every(freq: Freq, interval: u32) -> Self {
+        Self {
+            freq,
+            interval,
+            by_day: Vec::new(),
+            by_month_day: None,
+        }
+    }
+
+    /// The rule with a monthly series pinned to the day of month of `due`,
+    /// unless it already has one
+    pub fn pinned_to(&self, due: NaiveDate) -> Self {
+        let mut rule = self.clone();
+        if rule.freq == Freq::Monthly && rule.by_month_day.is_none() {
+            rule.by_month_day = Some(due.day());
+        }
+        rule
+    }
+
+    /// The first occurrence strictly after `after`
+    pub fn next(&self, after: NaiveDate) -> Option<NaiveDate> {
+        match self.freq {
+            Freq::Daily => after.checked_add_signed(Duration::days(self.interval as i64)),
+            Freq::Weekly if self.by_day.is_empty() => {
+                after.checked_add_signed(Duration::weeks(self.interval as i64))
+            }
+            Freq::Weekly => {
+                let weekday = after.weekday().num_days_from_monday() as i64;
+                let rest_of_week = (1..7 - weekday).map(Duration::days);
+                let next_week = (0..7)
+                    .map(|n| Duration::weeks(self.interval as i64) + Duration::days(n - weekday));
+                rest_of_week
+                    .chain(next_week)
+                    .filter_map(|delta| after.checked_add_signed(delta))
+                    .find(|d| self.by_day.contains(&d.weekday()))
+            }
+            Freq::Monthly => {
+                let month = after.checked_add_months(Months::new(self.interval))?;
+                match self.by_month_day {
+                    Some(day) => (1..=day).rev().find_map(|d| month.with_day(d)),
+                    None => Some(month),
+                }
+            }
+        }
+    }
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_weekday_code(code: &str) -> Option<Weekday> {
+    [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ]
+    .into_iter()
+    .find(|d| weekday_code(*d).eq_ignore_ascii_case(code))
+}
+
+// `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR`
+fn parse_rrule(rule: &str) -> Result<Recurrence, String> {
+    let mut freq = None;
+    let mut interval = 1;
+    let mut by_day = Vec::new();
+    let mut by_month_day = None;
+
+    for part in rule.split(';').filter(|p| !p.is_empty()) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid rule part '{}'", part))?;
+        match key.to_uppercase().as_str() {
+            "FREQ" => {
+                freq = Some(match value.to_uppercase().as_str() {
+                    "DAILY" => Freq::Daily,
+                    "WEEKLY" => Freq::Weekly,
+                    "MONTHLY" => Freq::Monthly,
+                    _ => return Err(format!("Unsupported FREQ '{}'", value)),
+                })
+            }
+            "INTERVAL" => {
+                interval = value
+                    .parse()
+                    .map_err(|_| format!("Invalid INTERVAL '{}'", value))?
+            }
+            "BYDAY" => {
+                for code in value.split(',') {
+                    by_day.push(
+                        parse_weekday_code(code)
+                            .ok_or_else(|| format!("Invalid BYDAY '{}'", code))?,
+                    );
+                }
+            }
+            "BYMONTHDAY" => {
+                by_month_day = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|d| (1..=31).contains(d))
+                        .ok_or_else(|| format!("Invalid BYMONTHDAY '{}'", value))?,
+                )
+            }
+            _ => return Err(format!("Unsupported rule part '{}'", key)),
+        }
+    }
+
+    let freq = freq.ok_or("Rule is missing FREQ")?;
+    Ok(Recurrence {
+        freq,
+        interval,
+        by_day,
+        by_month_day,
+    })
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    /// Accepts `daily`, `weekly`, `monthly`, `every 3 days`, `every 2 weeks`,
+    /// `every 6 months`, weekday lists like `mon,wed,fri`, or an RRULE
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim();
+        let lower = input.to_lowercase();
+        let rule = match lower.as_str() {
+            "daily" => Recurrence::every(Freq::Daily, 1),
+            "weekly" => Recurrence::every(Freq::Weekly, 1),
+            "monthly" => Recurrence::every(Freq::Monthly, 1),
+            _ if lower.contains("freq=") => parse_rrule(
+                input
+                    .trim_start_matches("RRULE:")
+                    .trim_start_matches("rrule:"),
+            )?,
+            _ if lower.starts_with("every ") => {
+                let words: Vec<&str> = lower.split_whitespace().collect();
+                let (n, unit) = match words.as_slice() {
+                    ["every", unit] => (1, *unit),
+                    ["every", n, unit] => (
+                        n.parse()
+                            .map_err(|_| format!("Invalid number '{}' in '{}'", n, input))?,
+                        *unit,
+                    ),
+                    _ => return Err(format!("Invalid recurrence '{}'", input)),
+                };
+                match unit.trim_end_matches('s') {
+                    "day" => Recurrence::every(Freq::Daily, n),
+                    "week" => Recurrence::every(Freq::Weekly, n),
+                    "month" => Recurrence::every(Freq::Monthly, n),
+                    _ => return Err(format!("Invalid unit '{}' in '{}'", unit, input)),
+                }
+            }
+            _ => {
+                let mut by_day = Vec::new();
+                for word in lower.split(',').map(str::trim) {
+                    by_day.push(
+                        parse_weekday(word)
+                            .ok_or_else(|| format!("Invalid recurrence '{}'", input))?,
+                    );
+                }
+                Recurrence {
+                    freq: Freq::Weekly,
+                    interval: 1,
+                    by_day,
+                    by_month_day: None,
+                }
+            }
+        };
+
+        if rule.interval == 0 || rule.interval > Recurrence::MAX_INTERVAL {
+            return Err(format!(
+                "Recurrence interval must be between 1 and {}",
+                Recurrence::MAX_INTERVAL
+            ));
+        }
+        if !rule.by_day.is_empty() && rule.freq != Freq::Weekly {
+            return Err("Weekdays can only be combined with a weekly rule".to_string());
+        }
+        if rule.by_month_day.is_some() && rule.freq != Freq::Monthly {
+            return Err("A day of the month can only be combined with a monthly rule".to_string());
+        }
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|d| d.to_string().to_lowercase())
+                .collect();
+            return match self.interval {
+                1 => write!(f, "{}", days.join(",")),
+                n => write!(f, "every {} weeks on {}", n, days.join(",")),
+            };
+        }
+        let unit = match self.freq {
+            Freq::Daily => "day",
+            Freq::Weekly => "week",
+            Freq::Monthly => "month",
+        };
+        match (self.interval, self.freq) {
+            (1, Freq::Daily) => write!(f, "daily"),
+            (1, Freq::Weekly) => write!(f, "weekly"),
+            (1, Freq::Monthly) => write!(f, "monthly"),
+            (n, _) => write!(f, "every {} {}s", n, unit),
+        }?;
+        match self.by_month_day {
+            Some(day) => write!(f, " on day {}", day),
+            None => Ok(()),
+        }
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(rule: Recurrence) -> Self {
+        let freq = match rule.freq {
+            Freq::Daily => "DAILY",
+            Freq::Weekly => "WEEKLY",
+            Freq::Monthly => "MONTHLY",
+        };
+        let mut s = format!("FREQ={}", freq);
+        if rule.interval != 1 {
+            s.push_str(&format!(";INTERVAL={}", rule.interval));
+        }
+        if !rule.by_day.is_empty() {
+            let days: Vec<&str> = rule.by_day.iter().map(|d| weekday_code(*d)).collect();
+            s.push_str(&format!(";BYDAY={}", days.join(",")));
+        }
+        if let Some(day) = rule.by_month_day {
+            s.push_str(&format!(";BYMONTHDAY={}", day));
+        }
+        s
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_words_and_rrules_alike() {
+        let words: Recurrence = "every 2 weeks".parse().unwrap();
+        let rrule: Recurrence = "RRULE:FREQ=WEEKLY;INTERVAL=2".parse().unwrap();
+        assert_eq!(words, rrule);
+        assert_eq!(String::from(words), "FREQ=WEEKLY;INTERVAL=2");
+
+        let days: Recurrence = "fri,mon".parse().unwrap();
+        assert_eq!(days.by_day, [Weekday::Fri, Weekday::Mon]);
+        assert_eq!(String::from(days), "FREQ=WEEKLY;BYDAY=FR,MO");
+    }
+
+    #[test]
+    fn rejects_inconsistent_rules() {
+        for input in [
+            "every 0 days",
+            "FREQ=YEARLY",
+            "INTERVAL=2",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=WEEKLY;BYMONTHDAY=3",
+            "FREQ=MONTHLY;BYMONTHDAY=32",
+            "every 2 fortnights",
+            "every 1001 days",
+            "every 4294967295 days",
+            "FREQ=MONTHLY;INTERVAL=4294967295",
+        ] {
+            assert!(input.parse::<Recurrence>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn month_days_round_trip() {
+        let rule: Recurrence = "FREQ=MONTHLY;BYMONTHDAY=31".parse().unwrap();
+        assert_eq!(rule.by_month_day, Some(31));
+        assert_eq!(rule.to_string(), "monthly on day 31");
+        assert_eq!(String::from(rule), "FREQ=MONTHLY;BYMONTHDAY=31");
+    }
+
+    #[test]
+    fn next_weekday_is_strictly_after() {
+        let rule: Recurrence = "mon,wed".parse().unwrap();
+        // 2026-10-14 is a Wednesday
+        assert_eq!(rule.next(day("2026-10-14")), Some(day("2026-10-19")));
+        assert_eq!(rule.next(day("2026-10-13")), Some(day("2026-10-14")));
+    }
+
+    #[test]
+    fn pinned_months_do_not_drift() {
+        let rule = Recurrence::every(Freq::Monthly, 1).pinned_to(day("2026-01-31"));
+        let mut due = day("2026-01-31");
+        let mut dues = Vec::new();
+        for _ in 0..4 {
+            due = rule.next(due).unwrap();
+            dues.push(due.to_string());
+        }
+        assert_eq!(
+            dues,
+            ["2026-02-28", "2026-03-31", "2026-04-30", "2026-05-31"]
+        );
+
+        // Without a pin the short month carries over
+        let loose = Recurrence::every(Freq::Monthly, 1);
+        assert_eq!(loose.next(day("2026-02-28")), Some(day("2026-03-28")));
+    }
+
+    #[test]
+    fn pinning_keeps_an_explicit_day_and_ignores_other_rules() {
+        let rule: Recurrence = "FREQ=MONTHLY;BYMONTHDAY=15".parse().unwrap();
+        assert_eq!(rule.pinned_to(day("2026-10-03")).by_month_day, Some(15));
+        let weekly: Recurrence = "weekly".parse().unwrap();
+        assert_eq!(weekly.pinned_to(day("2026-10-03")), weekly);
+    }
+
+    #[test]
+    fn weekdays_can_skip_weeks() {
+        let rule: Recurrence = "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR".parse().unwrap();
+        assert_eq!(rule.to_string(), "every 2 weeks on mon,fri");
+        // From Wednesday to the Friday of the same week, then two weeks on
+        assert_eq!(rule.next(day("2026-10-14")), Some(day("2026-10-16")));
+        assert_eq!(rule.next(day("2026-10-16")), Some(day("2026-10-26")));
+        assert_eq!(rule.next(day("2026-10-26")), Some(day("2026-10-30")));
+        assert_eq!(rule.next(day("2026-10-18")), Some(day("2026-10-26")));
+    }
+
+    #[test]
+    fn the_largest_interval_still_has_a_next_date() {
+        let rule: Recurrence = "every 1000 months".parse().unwrap();
+        assert_eq!(rule.next(day("2026-10-14")), Some(day("2110-02-14")));
+    }
+}
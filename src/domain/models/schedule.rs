@@ -0,0 +1,361 @@
+use crate::domain::dates::parse_weekday;
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How often a habit should be done.
+///
+/// Stored in its display form: `daily`, `every 3 days`, `3/week`,
+/// `2/month` or a weekday list like `mon,wed,fri`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(into = "String", try_from = "String")]
+pub enum Schedule {
+    #[default]
+    Daily,
+    /// Once in every stretch of N days, counted from the habit's creation
+    EveryDays(u32),
+    /// On N different days of each ISO week
+    PerWeek(u32),
+    /// On N different days of each calendar month
+    PerMonth(u32),
+    /// On each of these weekdays; other days don't count
+    Weekdays(Vec<Weekday>),
+}
+
+/// A stretch of days a schedule expects check-ins in, both ends inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Period {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl Period {
+    pub fn contains(&self, day: NaiveDate) -> bool {
+        self.start <= day && day <= self.end
+    }
+}
+
+impl Schedule {
+    /// Longest stretch `EveryDays` accepts, about ten years
+    pub const MAX_DAYS: u32 = 3650;
+
+    /// Days with a check-in a period needs to be complete
+    pub fn target(&self) -> u32 {
+        match self {
+            Schedule::PerWeek(n) | Schedule::PerMonth(n) => *n,
+            _ => 1,
+        }
+    }
+
+    /// The period containing `day`, or the last one before it for weekdays
+    /// off the schedule. `anchor` is the day `EveryDays` counts from.
+    pub fn latest(&self, day: NaiveDate, anchor: NaiveDate) -> Period {
+        let single = |day| Period {
+            start: day,
+            end: day,
+        };
+        match self {
+            Schedule::Daily => single(day),
+            Schedule::EveryDays(n) => {
+                // Checked, so a period reaching past the last date can't panic
+                let n = *n as i64;
+                let start = anchor
+                    .checked_add_signed(Duration::days((day - anchor).num_days().div_euclid(n) * n))
+                    .unwrap_or(anchor);
+                Period {
+                    start,
+                    end: start
+                        .checked_add_signed(Duration::days(n - 1))
+                        .unwrap_or(NaiveDate::MAX),
+                }
+            }
+            Schedule::PerWeek(_) => {
+                let start = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+                Period {
+                    start,
+                    end: start + Duration::days(6),
+                }
+            }
+            Schedule::PerMonth(_) => {
+                let start = day.with_day(1).unwrap_or(day);
+                let end = start
+                    .checked_add_months(Months::new(1))
+                    .and_then(|d| d.pred_opt())
+                    .unwrap_or(day);
+                Period { start, end }
+            }
+            Schedule::Weekdays(days) => single(
+                (0..7)
+                    .map(|back| day - Duration::days(back))
+                    .find(|d| days.contains(&d.weekday()))
+                    .unwrap_or(day),
+            ),
+        }
+    }
+
+    /// The period right before `period`
+    pub fn previous(&self, period: Period, anchor: NaiveDate) -> Period {
+        self.latest(period.start - Duration::days(1), anchor)
+    }
+
+    /// True when `day` is in a period, which is only false for weekdays off
+    /// the schedule
+    pub fn is_scheduled(&self, day: NaiveDate) -> bool {
+        match self {
+            Schedule::Weekdays(days) => days.contains(&day.weekday()),
+            _ => true,
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    /// Accepts `daily`, `weekly`, `monthly`, `every 3 days`, `3/week`,
+    /// `3 times a week`, `2/month`, `weekdays`, or a weekday list such as
+    /// `mon,wed,fri`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim();
+        let lower = input.to_lowercase();
+        let invalid = || {
+            format!(
+                "Invalid schedule '{}' (expected e.g. daily, \"every 2 days\", 3/week, 2/month or mon,wed,fri)",
+                input
+            )
+        };
+        let count = |n: &str| n.trim().parse::<u32>().map_err(|_| invalid());
+
+        let schedule = match lower.as_str() {
+            "daily" | "every day" => Schedule::Daily,
+            "weekly" => Schedule::PerWeek(1),
+            "monthly" => Schedule::PerMonth(1),
+            "weekdays" => Schedule::Weekdays(vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ]),
+            "weekends" => Schedule::Weekdays(vec![Weekday::Sat, Weekday::Sun]),
+            _ if lower.starts_with("every ") => {
+                match lower.split_whitespace().collect::<Vec<_>>().as_slice() {
+                    ["every", n, "days" | "day"] => match count(n)? {
+                        1 => Schedule::Daily,
+                        n => Schedule::EveryDays(n),
+                    },
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => {
+                // `3/week`, `3x/week`, `3 times a week`, `3 times per month`
+                let words: Vec<&str> = lower
+                    .split(|c: char| c == '/' || c.is_whitespace())
+                    .filter(|w| !w.is_empty() && !matches!(*w, "times" | "a" | "per"))
+                    .collect();
+                match words.as_slice() {
+                    [n, "week"] => Schedule::PerWeek(count(n.trim_end_matches('x'))?),
+                    [n, "month"] => Schedule::PerMonth(count(n.trim_end_matches('x'))?),
+                    _ => {
+                        let mut days = Vec::new();
+                        for word in lower.split(',').map(str::trim) {
+                            let day = parse_weekday(word).ok_or_else(invalid)?;
+                            if !days.contains(&day) {
+                                days.push(day);
+                            }
+                        }
+                        days.sort_by_key(|d| d.num_days_from_monday());
+                        Schedule::Weekdays(days)
+                    }
+                }
+            }
+        };
+
+        match schedule {
+            Schedule::EveryDays(0) | Schedule::PerWeek(0) | Schedule::PerMonth(0) => {
+                Err("A schedule needs at least one check-in per period".to_string())
+            }
+            Schedule::EveryDays(n) if n > Schedule::MAX_DAYS => Err(format!(
+                "Every {} days is too long (at most {})",
+                n,
+                Schedule::MAX_DAYS
+            )),
+            Schedule::PerWeek(n) if n > 7 => Err(format!("A week has no {} days", n)),
+            Schedule::PerMonth(n) if n > 28 => {
+                Err(format!("{}/month can't be met in every month", n))
+            }
+            schedule => Ok(schedule),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Daily => write!(f, "daily"),
+            Schedule::EveryDays(n) => write!(f, "every {} days", n),
+            Schedule::PerWeek(n) => write!(f, "{}/week", n),
+            Schedule::PerMonth(n) => write!(f, "{}/month", n),
+            Schedule::Weekdays(days) => {
+                let days: Vec<String> = days.iter().map(|d| d.to_string().to_lowercase()).collect();
+                write!(f, "{}", days.join(","))
+            }
+        }
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> Self {
+        schedule.to_string()
+    }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn period(start: &str, end: &str) -> Period {
+        Period {
+            start: day(start),
+            end: day(end),
+        }
+    }
+
+    #[test]
+    fn every_n_days_counts_from_the_anchor() {
+        let schedule = Schedule::EveryDays(3);
+        let anchor = day("2026-10-01");
+        assert_eq!(
+            schedule.latest(day("2026-10-05"), anchor),
+            period("2026-10-04", "2026-10-06")
+        );
+        assert_eq!(
+            schedule.latest(day("2026-10-07"), anchor),
+            period("2026-10-07", "2026-10-09")
+        );
+        // Days before the anchor still fall in whole periods
+        assert_eq!(
+            schedule.latest(day("2026-09-30"), anchor),
+            period("2026-09-28", "2026-09-30")
+        );
+    }
+
+    #[test]
+    fn weeks_run_monday_to_sunday() {
+        let schedule = Schedule::PerWeek(3);
+        let anchor = day("2026-01-01");
+        // 2026-10-14 is a Wednesday
+        let week = period("2026-10-12", "2026-10-18");
+        assert_eq!(schedule.latest(day("2026-10-14"), anchor), week);
+        assert_eq!(schedule.latest(day("2026-10-18"), anchor), week);
+        assert_eq!(
+            schedule.previous(week, anchor),
+            period("2026-10-05", "2026-10-11")
+        );
+    }
+
+    #[test]
+    fn months_are_calendar_months() {
+        let schedule = Schedule::PerMonth(2);
+        let anchor = day("2026-01-01");
+        assert_eq!(
+            schedule.latest(day("2026-02-14"), anchor),
+            period("2026-02-01", "2026-02-28")
+        );
+        assert_eq!(
+            schedule.latest(day("2026-12-31"), anchor),
+            period("2026-12-01", "2026-12-31")
+        );
+    }
+
+    #[test]
+    fn weekdays_off_the_schedule_belong_to_the_last_scheduled_day() {
+        let schedule: Schedule = "mon,wed,fri".parse().unwrap();
+        let anchor = day("2026-01-01");
+        let wednesday = period("2026-10-14", "2026-10-14");
+        assert_eq!(schedule.latest(day("2026-10-14"), anchor), wednesday);
+        assert_eq!(schedule.latest(day("2026-10-15"), anchor), wednesday);
+        assert!(!schedule.is_scheduled(day("2026-10-15")));
+        assert_eq!(
+            schedule.previous(wednesday, anchor),
+            period("2026-10-12", "2026-10-12")
+        );
+    }
+
+    #[test]
+    fn parses_the_documented_forms() {
+        let cases = [
+            ("daily", Schedule::Daily),
+            ("every 1 day", Schedule::Daily),
+            ("every 3 days", Schedule::EveryDays(3)),
+            ("weekly", Schedule::PerWeek(1)),
+            ("3/week", Schedule::PerWeek(3)),
+            ("3x/week", Schedule::PerWeek(3)),
+            ("3 times a week", Schedule::PerWeek(3)),
+            ("2 times per month", Schedule::PerMonth(2)),
+            ("monthly", Schedule::PerMonth(1)),
+            (
+                "Fri, mon,wed,mon",
+                Schedule::Weekdays(vec![Weekday::Mon, Weekday::Wed, Weekday::Fri]),
+            ),
+            (
+                "weekends",
+                Schedule::Weekdays(vec![Weekday::Sat, Weekday::Sun]),
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(input.parse::<Schedule>().unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_impossible_schedules() {
+        for input in [
+            "0/week",
+            "8/week",
+            "29/month",
+            "every 0 days",
+            "every 3651 days",
+            "every 100000000 days",
+            "sometimes",
+            "",
+        ] {
+            assert!(input.parse::<Schedule>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for input in ["daily", "every 3 days", "3/week", "2/month", "mon,wed,fri"] {
+            let schedule: Schedule = input.parse().unwrap();
+            assert_eq!(schedule.to_string(), input);
+        }
+    }
+
+    #[test]
+    fn long_stretches_do_not_overflow() {
+        assert_eq!(
+            "every 3650 days".parse::<Schedule>(),
+            Ok(Schedule::EveryDays(3650))
+        );
+        // Only reachable by building the schedule directly
+        let schedule = Schedule::EveryDays(100_000_000);
+        let anchor = day("2026-10-01");
+        let period = schedule.latest(day("2026-10-14"), anchor);
+        assert_eq!(period.start, anchor);
+        assert_eq!(period.end, NaiveDate::MAX);
+        assert!(period.contains(day("2026-10-14")));
+    }
+}
@@ -1,5 +1,8 @@
-use chrono::{DateTime, Local};
+use crate::domain::models::Recurrence;
+use chrono::{DateTime, Duration, Local};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Task {
@@ -11,8 +14,504 @@ pub struct Task {
     pub description: Option<String>,
     /// Creation timestamp
     pub created_at: DateTime<Local>,
-    /// Completion status
-    pub completed: bool,
-    /// Completion timestamp
-    pub completed_at: Option<DateTime<Local>>,
+    /// Workflow state
+    pub status: Status,
+    /// Every status change, oldest first
+    #[serde(default)]
+    pub history: Vec<Transition>,
+    /// Optional priority
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// Optional due date
+    #[serde(default)]
+    pub due: Option<DateTime<Local>>,
+    /// Repeat rule, completing the task creates the next occurrence
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// ID of the first task of the recurring series this one belongs to
+    #[serde(default)]
+    pub series_id: Option<u32>,
+    /// Free-form labels
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Dotted project path, e.g. `work.backend`
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Task this one is a subtask of
+    #[serde(default)]
+    pub parent_id: Option<u32>,
+    /// Tasks that must be done before this one can start
+    #[serde(default)]
+    pub depends_on: Vec<u32>,
+    /// Progress notes, oldest first
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// Time spent on the task, oldest first
+    #[serde(default)]
+    pub time_log: Vec<TimeEntry>,
+    /// Expected effort, compared with `time_log` by `stats estimates`
+    #[serde(default)]
+    pub estimate: Option<Estimate>,
+    /// Snoozed: hidden from `task read` and `next` until then
+    #[serde(default)]
+    pub wait_until: Option<DateTime<Local>>,
+}
+
+impl Task {
+    /// Todo, in progress or waiting
+    pub fn is_open(&self) -> bool {
+        self.status.is_open()
+    }
+
+    /// Move to `status`, recording the transition. Closed tasks have to be
+    /// reopened before they can move anywhere else.
+    pub fn set_status(&mut self, status: Status, at: DateTime<Local>) -> Result<(), String> {
+        if status == self.status {
+            return Err(format!("Task {} is already {}", self.id, status));
+        }
+        if !self.is_open() && status != Status::Todo {
+            return Err(format!(
+                "Task {} is {}, reopen it first",
+                self.id, self.status
+            ));
+        }
+        // Leaving in-progress stops the clock
+        if self.status == Status::InProgress {
+            self.stop_timer(at);
+        }
+        self.status = status;
+        self.history.push(Transition { status, at });
+        Ok(())
+    }
+
+    /// When a done task was completed
+    pub fn done_at(&self) -> Option<DateTime<Local>> {
+        if self.status != Status::Done {
+            return None;
+        }
+        self.history
+            .iter()
+            .rev()
+            .find(|t| t.status == Status::Done)
+            .map(|t| t.at)
+    }
+
+    /// The entry of a running timer
+    pub fn running_timer(&self) -> Option<&TimeEntry> {
+        self.time_log.iter().find(|e| e.end.is_none())
+    }
+
+    /// Close a running timer at `at`, returning the time it ran
+    pub fn stop_timer(&mut self, at: DateTime<Local>) -> Option<Duration> {
+        let entry = self.time_log.iter_mut().find(|e| e.end.is_none())?;
+        entry.end = Some(at.max(entry.start));
+        Some(entry.duration(at))
+    }
+
+    /// Time tracked between `since` and `until`, a running timer counting up
+    /// to `until`
+    pub fn tracked(&self, since: Option<DateTime<Local>>, until: DateTime<Local>) -> Duration {
+        self.time_log
+            .iter()
+            .map(|e| e.overlap(since, until))
+            .fold(Duration::zero(), |sum, d| sum + d)
+    }
+
+    /// ID shared by every occurrence of a recurring series
+    pub fn series(&self) -> u32 {
+        self.series_id.unwrap_or(self.id)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// True when the task belongs to `project` or one of its subprojects
+    pub fn in_project(&self, project: &str) -> bool {
+        self.project.as_deref().is_some_and(|p| {
+            p == project
+                || p.strip_prefix(project)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+
+    /// Open and snoozed past `now`
+    pub fn is_snoozed(&self, now: DateTime<Local>) -> bool {
+        self.is_open() && self.wait_until.is_some_and(|until| until > now)
+    }
+
+    /// Open and back from snoozing within the last day
+    pub fn is_new(&self, now: DateTime<Local>) -> bool {
+        self.is_open()
+            && self
+                .wait_until
+                .is_some_and(|until| until <= now && now - until < Duration::days(1))
+    }
+
+    /// Where the due date falls relative to `now`. Closed tasks and tasks
+    /// without a due date have no state.
+    pub fn due_state(&self, now: DateTime<Local>) -> Option<DueState> {
+        if !self.is_open() {
+            return None;
+        }
+        let due = self.due?.date_naive();
+        let today = now.date_naive();
+        Some(if due < today {
+            DueState::Overdue
+        } else if due == today {
+            DueState::Today
+        } else {
+            DueState::Upcoming
+        })
+    }
+}
+
+/// Where a task is in its workflow
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Todo,
+    InProgress,
+    Waiting,
+    Done,
+    Cancelled,
+}
+
+impl Status {
+    pub const ALL: [Status; 5] = [
+        Status::Todo,
+        Status::InProgress,
+        Status::Waiting,
+        Status::Done,
+        Status::Cancelled,
+    ];
+
+    pub fn is_open(self) -> bool {
+        !matches!(self, Status::Done | Status::Cancelled)
+    }
+
+    /// Short marker for tables: `[ ]`, `[>]`, `[~]`, `[x]`, `[-]`
+    pub fn marker(self) -> &'static str {
+        match self {
+            Status::Todo => "[ ]",
+            Status::InProgress => "[>]",
+            Status::Waiting => "[~]",
+            Status::Done => "[x]",
+            Status::Cancelled => "[-]",
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Status::Todo => "todo",
+            Status::InProgress => "in-progress",
+            Status::Waiting => "waiting",
+            Status::Done => "done",
+            Status::Cancelled => "cancelled",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Status {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "todo" => Ok(Status::Todo),
+            "in-progress" | "started" | "active" => Ok(Status::InProgress),
+            "waiting" => Ok(Status::Waiting),
+            "done" | "completed" => Ok(Status::Done),
+            "cancelled" | "canceled" => Ok(Status::Cancelled),
+            _ => Err(format!(
+                "Invalid status '{}' (expected todo, in-progress, waiting, done or cancelled)",
+                s
+            )),
+        }
+    }
+}
+
+/// A timestamped note on a task
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Annotation {
+    pub at: DateTime<Local>,
+    pub text: String,
+}
+
+/// Expected effort, as time or as a number of pomodoros.
+///
+/// Stored in its display form, e.g. `1h30m` or `4 pomodoros`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(into = "String", try_from = "String")]
+pub enum Estimate {
+    Minutes(u32),
+    Pomodoros(u32),
+}
+
+impl Estimate {
+    /// Largest estimate accepted, 10 000 hours
+    pub const MAX_MINUTES: u32 = 600_000;
+    /// Largest number of pomodoros accepted
+    pub const MAX_POMODOROS: u32 = 10_000;
+
+    /// The estimate as time, counting `pomodoro` for each pomodoro
+    pub fn duration(&self, pomodoro: Duration) -> Duration {
+        match self {
+            Estimate::Minutes(m) => Duration::minutes(*m as i64),
+            Estimate::Pomodoros(n) => Duration::minutes(pomodoro.num_minutes() * *n as i64),
+        }
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Estimate::Minutes(m) if m < 60 => write!(f, "{}m", m),
+            Estimate::Minutes(m) if m % 60 == 0 => write!(f, "{}h", m / 60),
+            Estimate::Minutes(m) => write!(f, "{}h{:02}m", m / 60, m % 60),
+            Estimate::Pomodoros(1) => write!(f, "1 pomodoro"),
+            Estimate::Pomodoros(n) => write!(f, "{} pomodoros", n),
+        }
+    }
+}
+
+impl FromStr for Estimate {
+    type Err = String;
+
+    /// Accepts `90m`, `2h`, `1h30m`, `1.5h`, `4p` or `4 pomodoros`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid estimate '{}' (expected e.g. 90m, 1h30m, 1.5h or 4p)",
+                s.trim()
+            )
+        };
+        let compact: String = s
+            .to_lowercase()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+
+        for suffix in ["pomodoros", "pomodoro", "pom", "p"] {
+            if let Some(n) = compact.strip_suffix(suffix) {
+                return match n.parse() {
+                    Ok(0) | Err(_) => Err(invalid()),
+                    Ok(n) if n > Estimate::MAX_POMODOROS => Err(format!(
+                        "Estimate can't be more than {} pomodoros",
+                        Estimate::MAX_POMODOROS
+                    )),
+                    Ok(n) => Ok(Estimate::Pomodoros(n)),
+                };
+            }
+        }
+
+        // `1h30m`, `1.5h`, `90m`
+        let (hours, rest) = match compact.split_once('h') {
+            Some((h, rest)) => (h.parse::<f64>().map_err(|_| invalid())?, rest),
+            None => (0.0, compact.as_str()),
+        };
+        let minutes = match rest.strip_suffix("min").or_else(|| rest.strip_suffix('m')) {
+            Some(m) => m.parse::<u32>().map_err(|_| invalid())?,
+            None if rest.is_empty() && compact.contains('h') => 0,
+            None => return Err(invalid()),
+        };
+        if !(0.0..=10_000.0).contains(&hours) {
+            return Err(invalid());
+        }
+        match ((hours * 60.0).round() as u32).checked_add(minutes) {
+            Some(0) => Err("Estimate must be more than zero".to_string()),
+            Some(total) if total <= Estimate::MAX_MINUTES => Ok(Estimate::Minutes(total)),
+            _ => Err(format!(
+                "Estimate can't be more than {}",
+                Estimate::Minutes(Estimate::MAX_MINUTES)
+            )),
+        }
+    }
+}
+
+impl From<Estimate> for String {
+    fn from(estimate: Estimate) -> Self {
+        estimate.to_string()
+    }
+}
+
+impl TryFrom<String> for Estimate {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// A stretch of time spent on a task
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimeEntry {
+    pub start: DateTime<Local>,
+    /// `None` while the timer is running
+    #[serde(default)]
+    pub end: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub source: TimeSource,
+}
+
+impl TimeEntry {
+    /// Length of the entry, a running one counting up to `now`
+    pub fn duration(&self, now: DateTime<Local>) -> Duration {
+        (self.end.unwrap_or(now) - self.start).max(Duration::zero())
+    }
+
+    /// The part of the entry that falls between `since` and `until`
+    pub fn overlap(&self, since: Option<DateTime<Local>>, until: DateTime<Local>) -> Duration {
+        let start = since.map_or(self.start, |s| self.start.max(s));
+        let end = self.end.unwrap_or(until).min(until);
+        (end - start).max(Duration::zero())
+    }
+}
+
+/// How a time entry was recorded
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeSource {
+    /// `task start` / `task stop`
+    #[default]
+    Timer,
+    /// A finished pomodoro work phase linked to the task
+    Pomodoro,
+}
+
+/// A status change and when it happened
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Transition {
+    pub status: Status,
+    pub at: DateTime<Local>,
+}
+
+/// Validate a tag, accepting an optional leading `+`
+pub fn parse_tag(s: &str) -> Result<String, String> {
+    let tag = s.trim().trim_start_matches('+');
+    if tag.is_empty() || tag.starts_with('-') {
+        return Err(format!("Invalid tag '{}'", s));
+    }
+    if tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(format!("Tag '{}' must not contain spaces or commas", s));
+    }
+    Ok(tag.to_string())
+}
+
+/// Validate a project path such as `work.backend`
+pub fn parse_project(s: &str) -> Result<String, String> {
+    let project = s.trim();
+    if project.split('.').any(|part| part.is_empty()) {
+        return Err(format!("Invalid project '{}'", s));
+    }
+    if project.contains(char::is_whitespace) {
+        return Err(format!("Project '{}' must not contain spaces", s));
+    }
+    Ok(project.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DueState {
+    Overdue,
+    Today,
+    Upcoming,
+}
+
+/// Task priority, ordered from most to least urgent
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    #[serde(rename = "H")]
+    High,
+    #[serde(rename = "M")]
+    Medium,
+    #[serde(rename = "L")]
+    Low,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Priority::High => "H",
+            Priority::Medium => "M",
+            Priority::Low => "L",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "h" | "high" => Ok(Priority::High),
+            "m" | "medium" => Ok(Priority::Medium),
+            "l" | "low" => Ok(Priority::Low),
+            _ => Err(format!("Invalid priority '{}' (expected H, M or L)", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_parse_as_time_or_pomodoros() {
+        let cases = [
+            ("90m", Estimate::Minutes(90)),
+            ("90 min", Estimate::Minutes(90)),
+            ("2H", Estimate::Minutes(120)),
+            ("1h30m", Estimate::Minutes(90)),
+            ("1.5h", Estimate::Minutes(90)),
+            ("0.1h", Estimate::Minutes(6)),
+            ("4p", Estimate::Pomodoros(4)),
+            ("1 pomodoro", Estimate::Pomodoros(1)),
+            ("10000h", Estimate::Minutes(Estimate::MAX_MINUTES)),
+            (
+                "10000 pomodoros",
+                Estimate::Pomodoros(Estimate::MAX_POMODOROS),
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(input.parse::<Estimate>(), Ok(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn estimates_past_the_caps_are_rejected() {
+        for input in [
+            "0m",
+            "0p",
+            "-1h",
+            "nanh",
+            "infh",
+            "10000h1m",
+            "600001m",
+            "4294967295m",
+            "9999h4294967295m",
+            "10001p",
+            "soon",
+        ] {
+            assert!(input.parse::<Estimate>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn estimates_display_in_their_stored_form() {
+        for (estimate, shown) in [
+            (Estimate::Minutes(45), "45m"),
+            (Estimate::Minutes(120), "2h"),
+            (Estimate::Minutes(90), "1h30m"),
+            (Estimate::Pomodoros(1), "1 pomodoro"),
+            (Estimate::Pomodoros(4), "4 pomodoros"),
+        ] {
+            assert_eq!(estimate.to_string(), shown);
+            assert_eq!(shown.parse::<Estimate>(), Ok(estimate));
+        }
+        let pomodoros = Estimate::Pomodoros(3).duration(Duration::minutes(25));
+        assert_eq!(pomodoros, Duration::minutes(75));
+    }
 }
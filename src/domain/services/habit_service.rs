@@ -1,13 +1,27 @@
-use crate::domain::models::Habit;
-use crate::storage::json_store::JsonStorage;
-use chrono::Local;
+use crate::domain::models::{CheckIn, Habit, Measure, Schedule};
+use crate::error::AppError;
+use crate::storage::Storage;
+use chrono::{Local, NaiveDate};
 
-pub struct HabitService {
-    storage: JsonStorage,
+/// Changes to apply to an existing habit, `None` leaves a field untouched
+#[derive(Debug, Default, Clone)]
+pub struct HabitChanges {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub schedule: Option<Schedule>,
+    /// New target amount, turning a plain habit into a measurable one
+    pub target: Option<f64>,
+    pub unit: Option<String>,
+    /// Make the habit a plain one again
+    pub clear_measure: bool,
 }
 
-impl HabitService {
-    pub fn new(storage: JsonStorage) -> Self {
+pub struct HabitService<S: Storage> {
+    storage: S,
+}
+
+impl<S: Storage> HabitService<S> {
+    pub fn new(storage: S) -> Self {
         Self { storage }
     }
 
@@ -15,8 +29,11 @@ impl HabitService {
         &self,
         name: String,
         description: Option<String>,
-        frequency: String,
-    ) -> Result<Habit, String> {
+        schedule: Schedule,
+        measure: Option<Measure>,
+    ) -> Result<Habit, AppError> {
+        check_measure(&schedule, measure.as_ref())?;
+        let _lock = self.storage.lock()?;
         let mut habits = self.storage.load_habits()?;
 
         let id = habits.iter().map(|h| h.id).max().unwrap_or(0) + 1;
@@ -25,10 +42,11 @@ impl HabitService {
             id,
             name,
             description,
-            frequency,
+            schedule,
             streak: 0,
             created_at: Local::now(),
-            last_completed_at: None,
+            log: Vec::new(),
+            measure,
         };
 
         habits.push(new_habit.clone());
@@ -37,29 +55,50 @@ impl HabitService {
         Ok(new_habit)
     }
 
-    pub fn list_habits(&self) -> Result<Vec<Habit>, String> {
-        self.storage.load_habits()
+    /// Every habit, with streaks broken by a missed period reset and saved
+    pub fn list_habits(&self) -> Result<Vec<Habit>, AppError> {
+        let _lock = self.storage.lock()?;
+        let mut habits = self.storage.load_habits()?;
+        let today = Local::now().date_naive();
+        let mut changed = false;
+        for habit in habits.iter_mut() {
+            let streak = habit.current_streak(today);
+            if habit.streak != streak {
+                habit.streak = streak;
+                changed = true;
+            }
+        }
+        if changed {
+            self.storage.save_habits(&habits)?;
+        }
+        Ok(habits)
     }
 
-    pub fn delete_habit(&self, id: u32) -> Result<(), String> {
+    pub fn delete_habit(&self, id: u32) -> Result<(), AppError> {
+        let _lock = self.storage.lock()?;
         let mut habits = self.storage.load_habits()?;
         if let Some(pos) = habits.iter().position(|h| h.id == id) {
             habits.remove(pos);
             self.storage.save_habits(&habits)?;
             Ok(())
         } else {
-            Err(format!("Habit with ID {} not found", id))
+            Err(AppError::NotFound(format!(
+                "Habit with ID {} not found",
+                id
+            )))
         }
     }
 
-    pub fn update_habit(
-        &self,
-        id: u32,
-        name: Option<String>,
-        description: Option<String>,
-        frequency: Option<String>,
-        increment_streak: bool,
-    ) -> Result<Habit, String> {
+    pub fn update_habit(&self, id: u32, changes: HabitChanges) -> Result<Habit, AppError> {
+        let HabitChanges {
+            name,
+            description,
+            schedule,
+            target,
+            unit,
+            clear_measure,
+        } = changes;
+        let _lock = self.storage.lock()?;
         let mut habits = self.storage.load_habits()?;
 
         if let Some(habit) = habits.iter_mut().find(|h| h.id == id) {
@@ -69,15 +108,44 @@ impl HabitService {
             if let Some(d) = description {
                 habit.description = Some(d);
             }
-            if let Some(f) = frequency {
-                habit.frequency = f;
+            let rescore = schedule.is_some() || target.is_some() || clear_measure;
+            if let Some(s) = schedule {
+                habit.schedule = s;
+            }
+            if clear_measure {
+                habit.measure = None;
+            } else if target.is_some() || unit.is_some() {
+                let current = habit.measure.take();
+                // Plain check-ins have no amount, so they would all count as 0
+                if current.is_none() && !habit.log.is_empty() {
+                    return Err(AppError::Validation(format!(
+                        "Habit {} already has check-ins without amounts; add a new habit to track a target",
+                        id
+                    )));
+                }
+                let target = target
+                    .or(current.as_ref().map(|m| m.target))
+                    .ok_or_else(|| {
+                        AppError::Validation(format!(
+                            "Habit {} has no target to give a unit to",
+                            id
+                        ))
+                    })?;
+                let unit = unit.or(current.map(|m| m.unit)).unwrap_or_default();
+                habit.measure = Some(Measure {
+                    target,
+                    unit: unit.trim().to_string(),
+                });
             }
-            if increment_streak {
-                habit.streak += 1;
-                habit.last_completed_at = Some(Local::now());
+            check_measure(&habit.schedule, habit.measure.as_ref())?;
+            if rescore {
+                habit.streak = habit.current_streak(Local::now().date_naive());
             }
         } else {
-            return Err(format!("Habit with ID {} not found", id));
+            return Err(AppError::NotFound(format!(
+                "Habit with ID {} not found",
+                id
+            )));
         }
 
         self.storage.save_habits(&habits)?;
@@ -86,7 +154,345 @@ impl HabitService {
             .iter()
             .find(|h| h.id == id)
             .cloned()
-            .ok_or("Habit lost")?;
+            .ok_or_else(|| AppError::NotFound(format!("Habit with ID {} not found", id)))?;
         Ok(updated_habit)
     }
+
+    /// Record the habit as done on `date`. A day already checked in, or a
+    /// period that already has all its check-ins, is left as it is; the flag
+    /// tells whether anything was recorded. Measurable habits need an
+    /// `amount` instead, which adds to whatever that day has so far.
+    pub fn check_in(
+        &self,
+        id: u32,
+        date: NaiveDate,
+        amount: Option<f64>,
+    ) -> Result<(Habit, bool), AppError> {
+        let _lock = self.storage.lock()?;
+        let mut habits = self.storage.load_habits()?;
+        let habit = habits
+            .iter_mut()
+            .find(|h| h.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("Habit with ID {} not found", id)))?;
+
+        let now = Local::now();
+        check_date(habit, date, now.date_naive())?;
+        if !habit.schedule.is_scheduled(date) {
+            return Err(AppError::Validation(format!(
+                "Habit {} isn't scheduled on {} ({})",
+                id,
+                date.format("%A"),
+                habit.schedule
+            )));
+        }
+        match (&habit.measure, amount) {
+            (Some(_), Some(amount)) => {
+                if !(amount.is_finite() && amount > 0.0) {
+                    return Err(AppError::Validation(format!(
+                        "Amount must be a positive number, got {}",
+                        amount
+                    )));
+                }
+                let existing = habit.log.iter_mut().find(|c| c.date == date);
+                let total = existing.as_ref().and_then(|c| c.amount).unwrap_or(0.0) + amount;
+                if !total.is_finite() {
+                    return Err(AppError::Validation(format!(
+                        "That amount would make the total for {} too large",
+                        date
+                    )));
+                }
+                match existing {
+                    Some(check_in) => {
+                        check_in.amount = Some(total);
+                        check_in.at = now;
+                    }
+                    None => habit.log.push(CheckIn {
+                        date,
+                        at: now,
+                        amount: Some(amount),
+                    }),
+                }
+            }
+            (Some(measure), None) => {
+                return Err(AppError::Validation(format!(
+                    "Habit {} is measured against {}; give the amount done",
+                    id, measure
+                )));
+            }
+            (None, Some(_)) => {
+                return Err(AppError::Validation(format!(
+                    "Habit {} has no target to count an amount towards",
+                    id
+                )));
+            }
+            (None, None) => {
+                if habit.is_checked_on(date) || habit.is_done_in(date) {
+                    return Ok((habit.clone(), false));
+                }
+                habit.log.push(CheckIn {
+                    date,
+                    at: now,
+                    amount: None,
+                });
+            }
+        }
+        habit.log.sort_by_key(|c| c.date);
+        habit.streak = habit.current_streak(now.date_naive());
+        let habit = habit.clone();
+
+        self.storage.save_habits(&habits)?;
+        Ok((habit, true))
+    }
+
+    /// Remove the check-in recorded for `date`
+    pub fn uncheck(&self, id: u32, date: NaiveDate) -> Result<Habit, AppError> {
+        let _lock = self.storage.lock()?;
+        let mut habits = self.storage.load_habits()?;
+        let habit = habits
+            .iter_mut()
+            .find(|h| h.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("Habit with ID {} not found", id)))?;
+
+        let today = Local::now().date_naive();
+        check_date(habit, date, today)?;
+        let Some(pos) = habit.log.iter().position(|c| c.date == date) else {
+            return Err(AppError::NotFound(format!(
+                "Habit {} has no check-in on {}",
+                id, date
+            )));
+        };
+        habit.log.remove(pos);
+        habit.streak = habit.current_streak(today);
+        let habit = habit.clone();
+
+        self.storage.save_habits(&habits)?;
+        Ok(habit)
+    }
+}
+
+// A measure needs a positive target, and a schedule asking for one day per
+// period, as the amounts of a period add up regardless of the days
+fn check_measure(schedule: &Schedule, measure: Option<&Measure>) -> Result<(), AppError> {
+    let Some(measure) = measure else {
+        return Ok(());
+    };
+    if !(measure.target.is_finite() && measure.target > 0.0) {
+        return Err(AppError::Validation(format!(
+            "Target must be a positive number, got {}",
+            measure.target
+        )));
+    }
+    if schedule.target() > 1 {
+        return Err(AppError::Validation(format!(
+            "A habit with a target can't be scheduled {}; use weekly or monthly and a target for the whole period",
+            schedule
+        )));
+    }
+    Ok(())
+}
+
+// Check-ins can't predate the habit or lie in the future
+fn check_date(habit: &Habit, date: NaiveDate, today: NaiveDate) -> Result<(), AppError> {
+    if date > today {
+        return Err(AppError::Validation(format!("{} is in the future", date)));
+    }
+    let created = habit.created_at.date_naive();
+    if date < created {
+        return Err(AppError::Validation(format!(
+            "Habit {} was created on {}, {} is before that",
+            habit.id, created, date
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory_store::MemoryStorage;
+    use chrono::{Datelike, Duration, Weekday};
+
+    // A service holding one habit created `age` days ago
+    fn service_with(schedule: &str, age: i64) -> (HabitService<MemoryStorage>, NaiveDate) {
+        let storage = MemoryStorage::new();
+        let now = Local::now();
+        storage
+            .save_habits(&[Habit {
+                id: 1,
+                name: "read".to_string(),
+                description: None,
+                schedule: schedule.parse().unwrap(),
+                streak: 0,
+                created_at: now - Duration::days(age),
+                log: Vec::new(),
+                measure: None,
+            }])
+            .unwrap();
+        (HabitService::new(storage), now.date_naive())
+    }
+
+    fn days_ago(today: NaiveDate, n: i64) -> NaiveDate {
+        today - Duration::days(n)
+    }
+
+    #[test]
+    fn created_habits_are_listed_and_deleted() {
+        let service = HabitService::new(MemoryStorage::new());
+        let habit = service
+            .create_habit("read".to_string(), None, Schedule::Daily, None)
+            .unwrap();
+        assert_eq!(habit.id, 1);
+        assert_eq!(service.list_habits().unwrap().len(), 1);
+        service.delete_habit(1).unwrap();
+        assert!(service.list_habits().unwrap().is_empty());
+        assert!(matches!(
+            service.delete_habit(1),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn a_second_check_in_on_the_same_day_is_a_no_op() {
+        let (service, today) = service_with("daily", 0);
+        let (_, recorded) = service.check_in(1, today, None).unwrap();
+        assert!(recorded);
+        let (habit, recorded) = service.check_in(1, today, None).unwrap();
+        assert!(!recorded);
+        assert_eq!(habit.log.len(), 1);
+        assert_eq!(habit.streak, 1);
+    }
+
+    #[test]
+    fn backfilled_check_ins_build_the_streak_and_can_be_undone() {
+        let (service, today) = service_with("daily", 10);
+        for n in [2, 1, 0] {
+            let (_, recorded) = service.check_in(1, days_ago(today, n), None).unwrap();
+            assert!(recorded);
+        }
+        assert_eq!(service.list_habits().unwrap()[0].streak, 3);
+
+        let habit = service.uncheck(1, days_ago(today, 1)).unwrap();
+        assert_eq!(habit.streak, 1);
+        assert!(matches!(
+            service.uncheck(1, days_ago(today, 1)),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn check_in_dates_must_lie_between_creation_and_today() {
+        let (service, today) = service_with("daily", 3);
+        let future = service.check_in(1, days_ago(today, -1), None);
+        assert!(matches!(future, Err(AppError::Validation(_))));
+        let too_early = service.check_in(1, days_ago(today, 4), None);
+        assert!(matches!(too_early, Err(AppError::Validation(_))));
+        assert!(service.check_in(1, days_ago(today, 3), None).is_ok());
+    }
+
+    #[test]
+    fn listing_resets_a_broken_streak() {
+        let (service, today) = service_with("daily", 10);
+        service.check_in(1, days_ago(today, 3), None).unwrap();
+        service.check_in(1, days_ago(today, 2), None).unwrap();
+        let habits = service.list_habits().unwrap();
+        assert_eq!(habits[0].streak, 0);
+        assert_eq!(service.storage.load_habits().unwrap()[0].streak, 0);
+    }
+
+    #[test]
+    fn measurable_habits_add_up_amounts_until_the_target() {
+        let (service, today) = service_with("daily", 5);
+        service
+            .update_habit(
+                1,
+                HabitChanges {
+                    target: Some(20.0),
+                    unit: Some("pages".to_string()),
+                    ..HabitChanges::default()
+                },
+            )
+            .unwrap();
+
+        let missing = service.check_in(1, today, None);
+        assert!(matches!(missing, Err(AppError::Validation(_))));
+        let negative = service.check_in(1, today, Some(-3.0));
+        assert!(matches!(negative, Err(AppError::Validation(_))));
+
+        let (habit, _) = service.check_in(1, today, Some(12.0)).unwrap();
+        assert!(!habit.is_done_in(today));
+        let (habit, _) = service.check_in(1, today, Some(8.0)).unwrap();
+        assert!(habit.is_done_in(today));
+        assert_eq!(habit.log.len(), 1);
+        assert_eq!(habit.log[0].amount, Some(20.0));
+        assert_eq!(habit.streak, 1);
+    }
+
+    #[test]
+    fn targets_need_a_schedule_of_one_check_in_per_period() {
+        let (service, _) = service_with("3/week", 5);
+        let changes = HabitChanges {
+            target: Some(5.0),
+            ..HabitChanges::default()
+        };
+        let result = service.update_habit(1, changes);
+        assert!(matches!(result, Err(AppError::Validation(_))));
+
+        let unit_only = HabitChanges {
+            unit: Some("km".to_string()),
+            ..HabitChanges::default()
+        };
+        assert!(matches!(
+            service.update_habit(1, unit_only),
+            Err(AppError::Validation(_))
+        ));
+
+        let plain = service.check_in(1, Local::now().date_naive(), Some(1.0));
+        assert!(matches!(plain, Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn weekday_schedules_reject_days_off() {
+        let (service, today) = service_with("mon", 14);
+        let off = (0..7)
+            .map(|n| days_ago(today, n))
+            .find(|d| d.weekday() != Weekday::Mon)
+            .unwrap();
+        let result = service.check_in(1, off, None);
+        assert!(matches!(result, Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn amounts_that_add_up_past_the_largest_number_are_rejected() {
+        let (service, today) = service_with("daily", 5);
+        service
+            .update_habit(
+                1,
+                HabitChanges {
+                    target: Some(2.0),
+                    ..HabitChanges::default()
+                },
+            )
+            .unwrap();
+        service.check_in(1, today, Some(0.5)).unwrap();
+        service.check_in(1, today, Some(1e308)).unwrap();
+        let overflow = service.check_in(1, today, Some(1e308));
+        assert!(matches!(overflow, Err(AppError::Validation(_))));
+        let habit = &service.list_habits().unwrap()[0];
+        assert_eq!(habit.log[0].amount, Some(0.5 + 1e308));
+    }
+
+    #[test]
+    fn a_target_cannot_be_added_to_plain_check_ins() {
+        let (service, today) = service_with("daily", 5);
+        service.check_in(1, today, None).unwrap();
+        let changes = HabitChanges {
+            target: Some(20.0),
+            ..HabitChanges::default()
+        };
+        let result = service.update_habit(1, changes);
+        assert!(matches!(result, Err(AppError::Validation(_))));
+        let habit = &service.list_habits().unwrap()[0];
+        assert!(habit.measure.is_none());
+        assert_eq!(habit.streak, 1);
+    }
 }
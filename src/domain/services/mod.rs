@@ -2,6 +2,6 @@ pub mod habit_service;
 pub mod pomodoro_service;
 pub mod task_service;
 
-pub use habit_service::HabitService;
+pub use habit_service::{HabitChanges, HabitService};
 pub use pomodoro_service::PomodoroService;
-pub use task_service::TaskService;
+pub use task_service::{NewTask, ReportGroup, TaskChanges, TaskService, TaskView, UpdateOutcome};
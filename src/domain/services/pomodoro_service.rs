@@ -1,13 +1,14 @@
-use crate::domain::models::{CompletedSession, PomodoroSession, Task};
-use crate::storage::json_store::JsonStorage;
-use chrono::{Duration, Utc};
+use crate::domain::models::{CompletedSession, PomodoroSession, Task, TimeEntry, TimeSource};
+use crate::error::AppError;
+use crate::storage::Storage;
+use chrono::{Duration, Local, Utc};
 
-pub struct PomodoroService {
-    storage: JsonStorage,
+pub struct PomodoroService<S: Storage> {
+    storage: S,
 }
 #[allow(dead_code)]
-impl PomodoroService {
-    pub fn new(storage: JsonStorage) -> Self {
+impl<S: Storage> PomodoroService<S> {
+    pub fn new(storage: S) -> Self {
         Self { storage }
     }
 
@@ -19,8 +20,27 @@ impl PomodoroService {
         long_break: u32,
         total_sessions: u32,
         sessions_before_long_break: u32,
-        task: Option<Task>,
-    ) -> Result<PomodoroSession, String> {
+        task_id: Option<u32>,
+    ) -> Result<PomodoroSession, AppError> {
+        // Zero would make an endless session, or divide by zero when
+        // picking the break length
+        for (name, value) in [
+            ("Work duration", work_duration),
+            ("Short break", short_break),
+            ("Long break", long_break),
+            ("Number of sessions", total_sessions),
+            ("Sessions before a long break", sessions_before_long_break),
+        ] {
+            if value == 0 {
+                return Err(AppError::Validation(format!("{} must be at least 1", name)));
+            }
+        }
+        let _lock = self.storage.lock()?;
+        if let Some(id) = task_id
+            && !self.storage.load_tasks()?.iter().any(|t| t.id == id)
+        {
+            return Err(AppError::NotFound(format!("Task with ID {} not found", id)));
+        }
         // Create new session
         let session = PomodoroSession {
             id: 1, // Simple ID for now
@@ -31,18 +51,11 @@ impl PomodoroService {
             sessions_before_long_break,
             current_session: 1,
             is_break: false,
-            task_id: task.as_ref().map(|t| t.id),
+            task_id,
             started_at: Utc::now(),
             completed_sessions: Vec::new(),
         };
 
-        // Save task if provided
-        if let Some(task) = task {
-            let mut tasks = self.storage.load_tasks()?;
-            tasks.push(task);
-            self.storage.save_tasks(&tasks)?;
-        }
-
         // Save session
         self.storage.save_current_session(&session)?;
 
@@ -50,15 +63,16 @@ impl PomodoroService {
     }
 
     // Get current session status
-    pub fn get_status(&self) -> Result<Option<PomodoroSession>, String> {
+    pub fn get_status(&self) -> Result<Option<PomodoroSession>, AppError> {
         self.storage.load_current_session()
     }
 
     // Complete current work session
-    pub fn complete_work_session(&self) -> Result<PomodoroSession, String> {
+    pub fn complete_work_session(&self) -> Result<PomodoroSession, AppError> {
+        let _lock = self.storage.lock()?;
         let mut session = match self.storage.load_current_session()? {
             Some(s) => s,
-            None => return Err("No active pomodoro session".to_string()),
+            None => return Err(AppError::NoActiveSession),
         };
 
         // Record completed work session
@@ -69,10 +83,22 @@ impl PomodoroService {
             was_break: false,
         };
 
+        // The work counts as time spent on the linked task, if it still exists
+        if let Some(id) = session.task_id
+            && let Some(mut task) = self.storage.load_tasks()?.into_iter().find(|t| t.id == id)
+        {
+            task.time_log.push(TimeEntry {
+                start: completed.start_time.with_timezone(&Local),
+                end: Some(completed.end_time.with_timezone(&Local)),
+                source: TimeSource::Pomodoro,
+            });
+            self.storage.save_task(&task)?;
+        }
+
         session.completed_sessions.push(completed);
 
         // Check if we need a long break or short break
-        if session.current_session % session.sessions_before_long_break == 0 {
+        if session.is_long_break() {
             // Time for long break
             session.is_break = true;
         } else {
@@ -85,18 +111,15 @@ impl PomodoroService {
     }
 
     // Complete current break
-    pub fn complete_break(&self) -> Result<PomodoroSession, String> {
+    pub fn complete_break(&self) -> Result<PomodoroSession, AppError> {
+        let _lock = self.storage.lock()?;
         let mut session = match self.storage.load_current_session()? {
             Some(s) => s,
-            None => return Err("No active pomodoro session".to_string()),
+            None => return Err(AppError::NoActiveSession),
         };
 
         // Record completed break
-        let break_duration = if session.current_session % session.sessions_before_long_break == 0 {
-            session.long_break
-        } else {
-            session.short_break
-        };
+        let break_duration = session.break_minutes();
 
         let completed = CompletedSession {
             session_number: session.current_session,
@@ -121,19 +144,114 @@ impl PomodoroService {
     }
 
     // Stop current session
-    pub fn stop_session(&self) -> Result<(), String> {
-        // Remove current session file
-        let path = format!("{}/current_session.json", self.storage.data_dir);
-        if std::path::Path::new(&path).exists() {
-            std::fs::remove_file(&path)
-                .map_err(|e| format!("Failed to remove session file: {}", e))?;
+    pub fn stop_session(&self) -> Result<(), AppError> {
+        let _lock = self.storage.lock()?;
+        if self.storage.load_current_session()?.is_none() {
+            return Err(AppError::NoActiveSession);
         }
-        Ok(())
+        self.storage.delete_current_session()
     }
 
     // List all tasks
-    pub fn list_tasks(&self) -> Result<Vec<Task>, String> {
+    pub fn list_tasks(&self) -> Result<Vec<Task>, AppError> {
         self.storage.load_tasks()
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::Status;
+    use crate::storage::memory_store::MemoryStorage;
+
+    fn service() -> PomodoroService<MemoryStorage> {
+        PomodoroService::new(MemoryStorage::new())
+    }
+
+    #[test]
+    fn zero_lengths_and_intervals_are_rejected() {
+        let service = service();
+        for args in [
+            (0, 5, 15, 4, 2),
+            (25, 0, 15, 4, 2),
+            (25, 5, 0, 4, 2),
+            (25, 5, 15, 0, 2),
+            (25, 5, 15, 4, 0),
+        ] {
+            let (work, short, long, total, interval) = args;
+            let result = service.start_session(work, short, long, total, interval, None);
+            assert!(matches!(result, Err(AppError::Validation(_))), "{:?}", args);
+        }
+        assert!(service.get_status().unwrap().is_none());
+    }
+
+    #[test]
+    fn sessions_can_only_be_linked_to_existing_tasks() {
+        let service = service();
+        let result = service.start_session(25, 5, 15, 4, 2, Some(3));
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn long_breaks_come_after_every_interval() {
+        let service = service();
+        service.start_session(25, 5, 15, 4, 2, None).unwrap();
+
+        let session = service.complete_work_session().unwrap();
+        assert!(session.is_break);
+        assert_eq!(session.break_minutes(), 5);
+        let session = service.complete_break().unwrap();
+        assert_eq!(session.current_session, 2);
+
+        let session = service.complete_work_session().unwrap();
+        assert_eq!(session.break_minutes(), 15);
+    }
+
+    #[test]
+    fn work_sessions_are_logged_on_the_linked_task() {
+        let storage = MemoryStorage::new();
+        let task = Task {
+            id: 1,
+            title: "write".to_string(),
+            description: None,
+            created_at: Local::now(),
+            status: Status::Todo,
+            history: Vec::new(),
+            priority: None,
+            due: None,
+            recurrence: None,
+            series_id: None,
+            tags: Vec::new(),
+            project: None,
+            parent_id: None,
+            depends_on: Vec::new(),
+            annotations: Vec::new(),
+            time_log: Vec::new(),
+            estimate: None,
+            wait_until: None,
+        };
+        storage.save_tasks(&[task]).unwrap();
+        let service = PomodoroService::new(storage.clone());
+
+        service.start_session(25, 5, 15, 4, 2, Some(1)).unwrap();
+        service.complete_work_session().unwrap();
+
+        let task = &storage.load_tasks().unwrap()[0];
+        assert_eq!(task.time_log.len(), 1);
+        assert_eq!(task.time_log[0].source, TimeSource::Pomodoro);
+        assert_eq!(task.time_log[0].duration(Local::now()).num_minutes(), 25);
+    }
+
+    #[test]
+    fn finishing_steps_needs_a_session() {
+        let service = service();
+        assert!(matches!(
+            service.complete_work_session(),
+            Err(AppError::NoActiveSession)
+        ));
+        assert!(matches!(
+            service.complete_break(),
+            Err(AppError::NoActiveSession)
+        ));
+    }
+}
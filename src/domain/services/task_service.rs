@@ -1,95 +1,1198 @@
-use crate::domain::models::Task;
-use crate::storage::json_store::JsonStorage;
-use chrono::Local;
+use crate::domain::filter::Filter;
+use crate::domain::models::{
+    Annotation, Estimate, Priority, Recurrence, Status, Task, TimeEntry, TimeSource, Transition,
+};
+use crate::domain::stats::{EstimateReport, estimate_report};
+use crate::domain::urgency::{Urgency, UrgencyWeights, urgency};
+use crate::error::AppError;
+use crate::storage::Storage;
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
 
-pub struct TaskService {
-    storage: JsonStorage,
+/// Fields for a new task
+#[derive(Debug, Default)]
+pub struct NewTask {
+    pub title: String,
+    pub description: Option<String>,
+    pub priority: Option<Priority>,
+    pub due: Option<DateTime<Local>>,
+    pub recurrence: Option<Recurrence>,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    pub parent_id: Option<u32>,
+    pub depends_on: Vec<u32>,
+    pub estimate: Option<Estimate>,
 }
 
-impl TaskService {
-    pub fn new(storage: JsonStorage) -> Self {
+/// Changes to apply to an existing task, `None` leaves a field untouched
+#[derive(Debug, Default, Clone)]
+pub struct TaskChanges {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub status: Option<Status>,
+    pub priority: Option<Priority>,
+    /// Remove the priority
+    pub clear_priority: bool,
+    pub due: Option<DateTime<Local>>,
+    /// Remove the due date
+    pub clear_due: bool,
+    pub recurrence: Option<Recurrence>,
+    /// Stop repeating the task
+    pub clear_recurrence: bool,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    pub project: Option<String>,
+    /// Take the task out of its project
+    pub clear_project: bool,
+    pub parent_id: Option<u32>,
+    /// Make the task top-level again
+    pub clear_parent: bool,
+    /// Give open subtasks the same closing status instead of refusing
+    pub cascade: bool,
+    pub add_depends_on: Vec<u32>,
+    pub remove_depends_on: Vec<u32>,
+    pub estimate: Option<Estimate>,
+    /// Drop the estimate
+    pub clear_estimate: bool,
+}
+
+/// Which tasks `TaskService::task_tree` shows besides the filter
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TaskView {
+    #[default]
+    All,
+    /// Todo or in-progress tasks whose dependencies are all closed
+    Ready,
+    /// Open tasks waiting on another open task
+    Blocked,
+}
+
+/// A task placed in the subtask tree of `TaskService::task_tree`
+#[derive(Debug)]
+pub struct TaskRow {
+    pub task: Task,
+    /// Nesting level, 0 for top-level rows
+    pub depth: usize,
+    /// `(done, total)` over all subtasks, `None` without subtasks
+    pub progress: Option<(usize, usize)>,
+    /// Open tasks this one waits on
+    pub blocked_by: Vec<Task>,
+}
+
+/// Task counts of a project, subprojects included
+#[derive(Debug)]
+pub struct ProjectSummary {
+    pub name: String,
+    pub open: usize,
+    pub done: usize,
+}
+
+/// One task changed by [`TaskService::update_tasks`]
+#[derive(Debug)]
+pub struct UpdateOutcome {
+    pub task: Task,
+    /// Next occurrence created by completing a recurring task
+    pub spawned: Option<Task>,
+    /// Tasks that were waiting on this one and can start now
+    pub unblocked: Vec<Task>,
+}
+
+/// Result of [`TaskService::start_timer`]
+#[derive(Debug)]
+pub struct TimerStart {
+    pub task: Task,
+    /// The task whose timer was stopped to start this one, and how long it ran
+    pub stopped: Option<(Task, Duration)>,
+}
+
+/// How [`TaskService::time_report`] groups tracked time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportGroup {
+    Task,
+    /// Tasks without a project are grouped as `(none)`
+    Project,
+    /// A task with several tags counts towards each of them
+    Tag,
+}
+
+/// Tracked time per group, see [`TaskService::time_report`]
+#[derive(Debug)]
+pub struct TimeReport {
+    /// `(group, time)`, most time first
+    pub rows: Vec<(String, Duration)>,
+    /// Time over all tasks, each counted once
+    pub total: Duration,
+}
+
+pub struct TaskService<S: Storage> {
+    storage: S,
+}
+
+impl<S: Storage> TaskService<S> {
+    pub fn new(storage: S) -> Self {
         Self { storage }
     }
 
-    pub fn create_task(&self, title: String, description: Option<String>) -> Result<Task, String> {
-        let mut tasks = self.storage.load_tasks()?;
+    pub fn create_task(&self, new: NewTask) -> Result<Task, AppError> {
+        let _lock = self.storage.lock()?;
+        let tasks = self.storage.load_tasks()?;
+
+        if let Some(parent) = new.parent_id
+            && !tasks.iter().any(|t| t.id == parent)
+        {
+            return Err(AppError::NotFound(format!(
+                "Parent task with ID {} not found",
+                parent
+            )));
+        }
+        // Nothing depends on a new task yet, so it can't close a cycle
+        if let Some(missing) = new
+            .depends_on
+            .iter()
+            .find(|d| !tasks.iter().any(|t| t.id == **d))
+        {
+            return Err(AppError::NotFound(format!(
+                "Dependency task with ID {} not found",
+                missing
+            )));
+        }
 
         let id = tasks.iter().map(|t| t.id).max().unwrap_or(0) + 1;
+        let now = Local::now();
 
         let new_task = Task {
             id,
-            title,
-            description,
-            created_at: Local::now(),
-            completed: false,
-            completed_at: None,
+            title: new.title,
+            description: new.description,
+            created_at: now,
+            status: Status::Todo,
+            history: vec![Transition {
+                status: Status::Todo,
+                at: now,
+            }],
+            priority: new.priority,
+            due: new.due,
+            recurrence: new.recurrence,
+            series_id: None,
+            tags: dedup(new.tags),
+            project: new.project,
+            parent_id: new.parent_id,
+            depends_on: dedup(new.depends_on),
+            annotations: Vec::new(),
+            time_log: Vec::new(),
+            estimate: new.estimate,
+            wait_until: None,
         };
 
-        tasks.push(new_task.clone());
-        self.storage.save_tasks(&tasks)?;
+        self.storage.save_task(&new_task)?;
 
         Ok(new_task)
     }
 
-    pub fn list_tasks(&self) -> Result<Vec<Task>, String> {
-        self.storage.load_tasks()
+    pub fn get_task(&self, id: u32) -> Result<Task, AppError> {
+        self.storage
+            .load_tasks()?
+            .into_iter()
+            .find(|t| t.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("Task with ID {} not found", id)))
     }
 
-    pub fn delete_task(&self, id: u32) -> Result<(), String> {
+    /// Tasks matching `filter`, open ones first, ordered by due date then priority
+    pub fn list_tasks(&self, filter: &Filter) -> Result<Vec<Task>, AppError> {
         let mut tasks = self.storage.load_tasks()?;
-        if let Some(pos) = tasks.iter().position(|t| t.id == id) {
-            tasks.remove(pos);
-            self.storage.save_tasks(&tasks)?;
+        tasks.retain(|t| filter.matches(t));
+        sort_tasks(&mut tasks);
+        Ok(tasks)
+    }
+
+    /// Tasks matching `filter` and `view` in the order of `list_tasks`, with
+    /// subtasks nested right below their parent. A subtask whose parent isn't
+    /// shown is moved to the top level. Snoozed tasks are left out unless
+    /// `include_snoozed` is set.
+    pub fn task_tree(
+        &self,
+        filter: &Filter,
+        view: TaskView,
+        include_snoozed: bool,
+    ) -> Result<Vec<TaskRow>, AppError> {
+        let all = self.storage.load_tasks()?;
+        let now = Local::now();
+        let in_view = |t: &Task| {
+            (include_snoozed || !t.is_snoozed(now))
+                && match view {
+                    TaskView::All => true,
+                    TaskView::Ready => is_actionable(t) && blockers(&all, t).next().is_none(),
+                    TaskView::Blocked => t.is_open() && blockers(&all, t).next().is_some(),
+                }
+        };
+        let mut matched: Vec<Task> = all
+            .iter()
+            .filter(|t| filter.matches(t) && in_view(t))
+            .cloned()
+            .collect();
+        sort_tasks(&mut matched);
+
+        let shown: HashSet<u32> = matched.iter().map(|t| t.id).collect();
+        let is_root = |t: &Task| t.parent_id.is_none_or(|p| !shown.contains(&p));
+        let mut rows = Vec::with_capacity(matched.len());
+        // Depth-first, children pushed in reverse to come out in sorted order
+        let mut stack: Vec<(&Task, usize)> = matched
+            .iter()
+            .rev()
+            .filter(|t| is_root(t))
+            .map(|t| (t, 0))
+            .collect();
+        while let Some((task, depth)) = stack.pop() {
+            stack.extend(
+                matched
+                    .iter()
+                    .rev()
+                    .filter(|t| t.parent_id == Some(task.id) && !is_root(t))
+                    .map(|t| (t, depth + 1)),
+            );
+            let subtasks = descendants(&all, task.id);
+            let progress = (!subtasks.is_empty()).then(|| {
+                let done = all
+                    .iter()
+                    .filter(|t| !t.is_open() && subtasks.contains(&t.id))
+                    .count();
+                (done, subtasks.len())
+            });
+            rows.push(TaskRow {
+                task: task.clone(),
+                depth,
+                progress,
+                blocked_by: blockers(&all, task).cloned().collect(),
+            });
+        }
+        Ok(rows)
+    }
+
+    /// The `limit` most urgent actionable tasks: todo or in progress, not
+    /// snoozed, not waiting on another task and without open subtasks
+    pub fn next_tasks(
+        &self,
+        weights: &UrgencyWeights,
+        limit: usize,
+    ) -> Result<Vec<(Task, Urgency)>, AppError> {
+        let tasks = self.storage.load_tasks()?;
+        let now = Local::now();
+        let mut scored: Vec<(Task, Urgency)> = tasks
+            .iter()
+            .filter(|t| {
+                is_actionable(t)
+                    && !t.is_snoozed(now)
+                    && blockers(&tasks, t).next().is_none()
+                    && !descendants(&tasks, t.id)
+                        .iter()
+                        .any(|id| tasks.iter().any(|s| s.id == *id && s.is_open()))
+            })
+            .map(|t| (t.clone(), urgency(t, &tasks, weights, now)))
+            .collect();
+        scored.sort_by(|a, b| b.1.score.total_cmp(&a.1.score).then(a.0.id.cmp(&b.0.id)));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
+    /// Open/done counts for every project and each of its parents, by name
+    pub fn project_summary(&self) -> Result<Vec<ProjectSummary>, AppError> {
+        let tasks = self.storage.load_tasks()?;
+        let mut summary: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for task in &tasks {
+            let Some(project) = &task.project else {
+                continue;
+            };
+            // `work.backend` counts towards `work` as well
+            for (end, _) in project.match_indices('.').chain([(project.len(), "")]) {
+                let counts = summary.entry(project[..end].to_string()).or_default();
+                if task.is_open() {
+                    counts.0 += 1;
+                } else {
+                    counts.1 += 1;
+                }
+            }
+        }
+        Ok(summary
+            .into_iter()
+            .map(|(name, (open, done))| ProjectSummary { name, open, done })
+            .collect())
+    }
+
+    /// Add a timestamped note to a task
+    pub fn annotate(&self, id: u32, text: &str) -> Result<Task, AppError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(AppError::Validation(
+                "Annotation text must not be empty".to_string(),
+            ));
+        }
+        self.modify_task(id, |task| {
+            task.annotations.push(Annotation {
+                at: Local::now(),
+                text: text.to_string(),
+            });
             Ok(())
-        } else {
-            Err(format!("Task with ID {} not found", id))
+        })
+    }
+
+    /// Remove the `number`th annotation (1-based, as listed by `task show`)
+    pub fn denotate(&self, id: u32, number: usize) -> Result<Annotation, AppError> {
+        let mut removed = None;
+        self.modify_task(id, |task| {
+            if number == 0 || number > task.annotations.len() {
+                return Err(AppError::NotFound(format!(
+                    "Task {} has no annotation {}",
+                    id, number
+                )));
+            }
+            removed = Some(task.annotations.remove(number - 1));
+            Ok(())
+        })?;
+        removed
+            .ok_or_else(|| AppError::NotFound(format!("Task {} has no annotation {}", id, number)))
+    }
+
+    /// Start timing a task and mark it in progress. Only one timer runs at a
+    /// time, so a timer running on another task is stopped first.
+    pub fn start_timer(&self, id: u32) -> Result<TimerStart, AppError> {
+        let _lock = self.storage.lock()?;
+        let mut tasks = self.storage.load_tasks()?;
+        let now = Local::now();
+
+        let task = tasks
+            .iter_mut()
+            .find(|t| t.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("Task with ID {} not found", id)))?;
+        if task.running_timer().is_some() {
+            return Err(AppError::Conflict(format!(
+                "The timer is already running on task {}",
+                id
+            )));
         }
+        if task.status != Status::InProgress {
+            task.set_status(Status::InProgress, now)
+                .map_err(AppError::Validation)?;
+        }
+        task.time_log.push(TimeEntry {
+            start: now,
+            end: None,
+            source: TimeSource::Timer,
+        });
+        let task = task.clone();
+
+        let stopped = tasks
+            .iter_mut()
+            .find(|t| t.id != id && t.running_timer().is_some())
+            .and_then(|other| Some((other.stop_timer(now)?, other.clone())));
+        if let Some((_, other)) = &stopped {
+            self.storage.save_task(other)?;
+        }
+        self.storage.save_task(&task)?;
+        Ok(TimerStart {
+            task,
+            stopped: stopped.map(|(ran, other)| (other, ran)),
+        })
+    }
+
+    /// Stop the running timer, returning its task and how long it ran
+    pub fn stop_timer(&self) -> Result<(Task, Duration), AppError> {
+        let _lock = self.storage.lock()?;
+        let mut task = self
+            .storage
+            .load_tasks()?
+            .into_iter()
+            .find(|t| t.running_timer().is_some())
+            .ok_or_else(|| AppError::NotFound("No timer is running".to_string()))?;
+        let ran = task.stop_timer(Local::now()).unwrap_or_else(Duration::zero);
+        self.storage.save_task(&task)?;
+        Ok((task, ran))
     }
 
-    pub fn update_task(
+    /// Time tracked between `since` and `until`, from timers and pomodoros
+    /// alike, grouped by `by`. Running timers count up to `until`.
+    pub fn time_report(
+        &self,
+        since: Option<DateTime<Local>>,
+        until: DateTime<Local>,
+        by: ReportGroup,
+    ) -> Result<TimeReport, AppError> {
+        let mut groups: BTreeMap<String, Duration> = BTreeMap::new();
+        let mut total = Duration::zero();
+        for task in self.storage.load_tasks()? {
+            let tracked = task.tracked(since, until);
+            if tracked.is_zero() {
+                continue;
+            }
+            total += tracked;
+            let keys = match by {
+                ReportGroup::Task => vec![format!("#{} {}", task.id, task.title)],
+                ReportGroup::Project => vec![task.project.unwrap_or_else(|| "(none)".to_string())],
+                ReportGroup::Tag if task.tags.is_empty() => vec!["(none)".to_string()],
+                ReportGroup::Tag => task.tags.iter().map(|t| format!("+{}", t)).collect(),
+            };
+            for key in keys {
+                *groups.entry(key).or_insert_with(Duration::zero) += tracked;
+            }
+        }
+        let mut rows: Vec<(String, Duration)> = groups.into_iter().collect();
+        rows.sort_by_key(|(_, time)| Reverse(*time));
+        Ok(TimeReport { rows, total })
+    }
+
+    /// Hide a task until `until`, or bring it back now with `None`
+    pub fn snooze(&self, id: u32, until: Option<DateTime<Local>>) -> Result<Task, AppError> {
+        self.modify_task(id, |task| {
+            if !task.is_open() {
+                return Err(AppError::Validation(format!(
+                    "Task {} is {}, there is nothing to snooze",
+                    id, task.status
+                )));
+            }
+            if until.is_some_and(|until| until <= Local::now()) {
+                return Err(AppError::Validation(
+                    "The snooze date must be in the future".to_string(),
+                ));
+            }
+            task.wait_until = until;
+            Ok(())
+        })
+    }
+
+    /// Estimates of the tasks matching `filter` against their tracked time,
+    /// see [`estimate_report`]
+    pub fn estimate_report(
+        &self,
+        filter: &Filter,
+        pomodoro: Duration,
+        weeks: usize,
+    ) -> Result<EstimateReport, AppError> {
+        let mut tasks = self.storage.load_tasks()?;
+        tasks.retain(|t| filter.matches(t));
+        Ok(estimate_report(&tasks, pomodoro, weeks, Local::now()))
+    }
+
+    // Load, change and save a single task under the lock
+    fn modify_task(
         &self,
         id: u32,
-        title: Option<String>,
-        description: Option<String>,
-        completed: Option<bool>,
-    ) -> Result<Task, String> {
+        change: impl FnOnce(&mut Task) -> Result<(), AppError>,
+    ) -> Result<Task, AppError> {
+        let _lock = self.storage.lock()?;
+        let mut task = self
+            .storage
+            .load_tasks()?
+            .into_iter()
+            .find(|t| t.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("Task with ID {} not found", id)))?;
+        change(&mut task)?;
+        self.storage.save_task(&task)?;
+        Ok(task)
+    }
+
+    /// Delete a task, see [`TaskService::delete_tasks`]
+    pub fn delete_task(&self, id: u32, cascade: bool) -> Result<Vec<u32>, AppError> {
+        self.delete_tasks(&[id], cascade)
+    }
+
+    /// Delete several tasks at once, failing without changes if any is
+    /// missing. Tasks with subtasks are refused unless `cascade` is set, which
+    /// deletes the subtasks as well. Returns the IDs of every deleted task.
+    pub fn delete_tasks(&self, ids: &[u32], cascade: bool) -> Result<Vec<u32>, AppError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let _lock = self.storage.lock()?;
+        let mut tasks = self.storage.load_tasks()?;
+        if let Some(missing) = ids.iter().find(|id| !tasks.iter().any(|t| t.id == **id)) {
+            return Err(AppError::NotFound(format!(
+                "Task with ID {} not found",
+                missing
+            )));
+        }
+
+        let mut doomed: Vec<u32> = ids.to_vec();
+        for &id in ids {
+            let subtasks = descendants(&tasks, id);
+            if cascade {
+                for subtask in subtasks {
+                    if !doomed.contains(&subtask) {
+                        doomed.push(subtask);
+                    }
+                }
+            } else if let Some(kept) = subtasks.iter().find(|s| !ids.contains(s)) {
+                return Err(AppError::Conflict(format!(
+                    "Task {} has subtasks (e.g. {}), use --cascade to delete them too",
+                    id, kept
+                )));
+            }
+        }
+        tasks.retain(|t| !doomed.contains(&t.id));
+        // Deleted tasks no longer hold anything up
+        for task in tasks.iter_mut() {
+            task.depends_on.retain(|d| !doomed.contains(d));
+        }
+        self.storage.save_tasks(&tasks)?;
+        Ok(doomed)
+    }
+
+    /// Apply the same changes to several tasks, failing without changes if
+    /// any is missing. Completing a task with open subtasks is refused unless
+    /// `changes.cascade` is set, which completes them as well.
+    pub fn update_tasks(
+        &self,
+        ids: &[u32],
+        changes: TaskChanges,
+    ) -> Result<Vec<UpdateOutcome>, AppError> {
+        let _lock = self.storage.lock()?;
         let mut tasks = self.storage.load_tasks()?;
+        if let Some(missing) = ids.iter().find(|id| !tasks.iter().any(|t| t.id == **id)) {
+            return Err(AppError::NotFound(format!(
+                "Task with ID {} not found",
+                missing
+            )));
+        }
 
-        if let Some(task) = tasks.iter_mut().find(|t| t.id == id) {
-            if let Some(t) = title {
-                task.title = t;
+        if let Some(parent) = changes.parent_id {
+            if !tasks.iter().any(|t| t.id == parent) {
+                return Err(AppError::NotFound(format!(
+                    "Parent task with ID {} not found",
+                    parent
+                )));
             }
-            if let Some(d) = description {
-                task.description = Some(d);
+            if let Some(id) = ids
+                .iter()
+                .find(|id| **id == parent || descendants(&tasks, **id).contains(&parent))
+            {
+                return Err(AppError::Validation(format!(
+                    "Task {} can't become a subtask of {}: that would create a cycle",
+                    id, parent
+                )));
             }
-            if let Some(c) = completed {
-                task.completed = c;
-                if c {
-                    task.completed_at = Some(Local::now());
-                } else {
-                    task.completed_at = None;
+        }
+
+        if let Some(missing) = changes
+            .add_depends_on
+            .iter()
+            .find(|d| !tasks.iter().any(|t| t.id == **d))
+        {
+            return Err(AppError::NotFound(format!(
+                "Dependency task with ID {} not found",
+                missing
+            )));
+        }
+        for &id in ids {
+            // `id` may not wait on a task that already waits on `id`
+            if let Some(dep) = changes
+                .add_depends_on
+                .iter()
+                .find(|d| **d == id || depends_transitively(&tasks, **d, id))
+            {
+                return Err(AppError::Validation(format!(
+                    "Task {} can't depend on {}: that would create a cycle",
+                    id, dep
+                )));
+            }
+        }
+
+        // Closing a parent needs its open subtasks closed too
+        let mut subtasks_to_close = Vec::new();
+        if let Some(status) = changes.status.filter(|s| !s.is_open()) {
+            for &id in ids {
+                let open = descendants(&tasks, id).into_iter().filter(|s| {
+                    !ids.contains(s) && tasks.iter().any(|t| t.id == *s && t.is_open())
+                });
+                for subtask in open {
+                    if !changes.cascade {
+                        return Err(AppError::Conflict(format!(
+                            "Task {} has open subtasks (e.g. {}), use --cascade to mark them {} too",
+                            id, subtask, status
+                        )));
+                    }
+                    if !subtasks_to_close.contains(&subtask) {
+                        subtasks_to_close.push(subtask);
+                    }
                 }
             }
-            let __updated_task = task.clone();
-            // multiple immutable borrows due to save_tasks needing &tasks later?
-            // no, we mutate `tasks`, then save it.
-            // slice needs to be taken from `tasks`.
-        } else {
-            return Err(format!("Task with ID {} not found", id));
         }
 
-        // Must re-find or scope the borrow if we want to return the task and save
-        // Actually, we can clone the found task inside the match, but we modified it in place.
-        // Let's just save.
-        self.storage.save_tasks(&tasks)?;
+        let close_only = TaskChanges {
+            status: changes.status,
+            ..TaskChanges::default()
+        };
+        let targets: Vec<(u32, &TaskChanges)> = ids
+            .iter()
+            .map(|id| (*id, &changes))
+            .chain(subtasks_to_close.iter().map(|id| (*id, &close_only)))
+            .collect();
 
-        // Retrieve again to return fresh? Or just find again?
-        // Since we modified in place, we can find it again.
-        let updated_task = tasks
+        let was_blocked: HashSet<u32> = tasks
             .iter()
-            .find(|t| t.id == id)
-            .cloned()
-            .ok_or("Task lost")?;
-        Ok(updated_task)
+            .filter(|t| blockers(&tasks, t).next().is_some())
+            .map(|t| t.id)
+            .collect();
+
+        // Apply everything before writing, so a rejected status change
+        // leaves the stored tasks untouched
+        let now = Local::now();
+        let mut changed = Vec::with_capacity(targets.len());
+        for (id, changes) in targets {
+            let Some(task) = tasks.iter_mut().find(|t| t.id == id) else {
+                continue;
+            };
+            let was_open = task.is_open();
+            apply_changes(task, changes.clone(), now).map_err(AppError::Validation)?;
+            changed.push((task.clone(), was_open && task.status == Status::Done));
+        }
+
+        let mut outcomes = Vec::with_capacity(changed.len());
+        for (updated_task, just_done) in changed {
+            let spawned = if just_done {
+                next_occurrence(&updated_task, &tasks)
+            } else {
+                None
+            };
+            if let Some(next) = &spawned {
+                tasks.push(next.clone());
+            }
+
+            outcomes.push(UpdateOutcome {
+                task: updated_task,
+                spawned,
+                unblocked: Vec::new(),
+            });
+        }
+
+        // One write for the whole batch, so it lands all at once or not at all
+        self.storage.save_tasks(&tasks)?;
+
+        // Credit each task that can start now to the first blocker it waited on
+        let mut reported = HashSet::new();
+        for outcome in outcomes.iter_mut() {
+            outcome.unblocked = tasks
+                .iter()
+                .filter(|t| {
+                    was_blocked.contains(&t.id)
+                        && t.depends_on.contains(&outcome.task.id)
+                        && blockers(&tasks, t).next().is_none()
+                        && reported.insert(t.id)
+                })
+                .cloned()
+                .collect();
+        }
+        Ok(outcomes)
+    }
+}
+
+/// Apply `changes` to `task`, failing on a status change its workflow
+/// doesn't allow
+fn apply_changes(
+    task: &mut Task,
+    changes: TaskChanges,
+    now: DateTime<Local>,
+) -> Result<(), String> {
+    if let Some(t) = changes.title {
+        task.title = t;
+    }
+    if let Some(d) = changes.description {
+        task.description = Some(d);
+    }
+    if let Some(p) = changes.priority {
+        task.priority = Some(p);
+    }
+    if changes.clear_priority {
+        task.priority = None;
+    }
+    if changes.due.is_some() || changes.clear_due {
+        task.due = changes.due;
+        // A monthly series follows the new day of month from now on
+        if let Some(rule) = task.recurrence.as_mut() {
+            rule.by_month_day = None;
+        }
+    }
+    if let Some(r) = changes.recurrence {
+        task.recurrence = Some(r);
+    }
+    if changes.clear_recurrence {
+        task.recurrence = None;
+    }
+    task.tags.extend(changes.add_tags);
+    task.tags = dedup(std::mem::take(&mut task.tags));
+    task.tags.retain(|t| !changes.remove_tags.contains(t));
+    if let Some(p) = changes.project {
+        task.project = Some(p);
+    }
+    if changes.clear_project {
+        task.project = None;
+    }
+    if let Some(p) = changes.parent_id {
+        task.parent_id = Some(p);
+    }
+    if changes.clear_parent {
+        task.parent_id = None;
+    }
+    task.depends_on.extend(changes.add_depends_on);
+    task.depends_on = dedup(std::mem::take(&mut task.depends_on));
+    task.depends_on
+        .retain(|d| !changes.remove_depends_on.contains(d));
+    if let Some(e) = changes.estimate {
+        task.estimate = Some(e);
+    }
+    if changes.clear_estimate {
+        task.estimate = None;
+    }
+    if let Some(status) = changes.status {
+        task.set_status(status, now)?;
+    }
+    Ok(())
+}
+
+/// Build the next occurrence of a recurring task that was just completed.
+///
+/// The due date rolls forward by the rule until it lands after today, so a
+/// task done late doesn't spawn an already overdue copy. Undated tasks
+/// are scheduled from the completion date. Nothing is created while the
+/// series still has an open occurrence. Monthly series are pinned to the day
+/// of month they were due on, so a short month doesn't shift them for good.
+fn next_occurrence(done: &Task, tasks: &[Task]) -> Option<Task> {
+    let rule = done.recurrence.as_ref()?;
+    let series = done.series();
+    if tasks
+        .iter()
+        .any(|t| t.series() == series && t.is_open() && t.id != done.id)
+    {
+        return None;
+    }
+
+    let now = Local::now();
+    let (base, time) = match done.due {
+        Some(due) => (due.date_naive(), due.time()),
+        None => (now.date_naive(), NaiveTime::MIN),
+    };
+    let rule = match done.due {
+        Some(due) => rule.pinned_to(due.date_naive()),
+        None => rule.clone(),
+    };
+    let mut day = rule.next(base)?;
+    while day <= now.date_naive() {
+        day = rule.next(day)?;
+    }
+    let due = Local.from_local_datetime(&day.and_time(time)).earliest()?;
+
+    Some(Task {
+        id: tasks.iter().map(|t| t.id).max().unwrap_or(0) + 1,
+        title: done.title.clone(),
+        description: done.description.clone(),
+        created_at: now,
+        status: Status::Todo,
+        history: vec![Transition {
+            status: Status::Todo,
+            at: now,
+        }],
+        priority: done.priority,
+        due: Some(due),
+        recurrence: Some(rule),
+        series_id: Some(series),
+        tags: done.tags.clone(),
+        project: done.project.clone(),
+        parent_id: done.parent_id,
+        depends_on: Vec::new(),
+        annotations: Vec::new(),
+        time_log: Vec::new(),
+        estimate: done.estimate,
+        wait_until: None,
+    })
+}
+
+// Open tasks first, then by due date, priority and ID. `None` sorts before
+// `Some`, so it's flipped to put undated/unprioritized tasks last.
+fn sort_tasks(tasks: &mut [Task]) {
+    tasks.sort_by_key(|t| {
+        (
+            !t.is_open(),
+            t.due.is_none(),
+            t.due,
+            t.priority.is_none(),
+            t.priority,
+            t.id,
+        )
+    });
+}
+
+/// Todo or in progress, tasks marked waiting are held up elsewhere
+fn is_actionable(task: &Task) -> bool {
+    matches!(task.status, Status::Todo | Status::InProgress)
+}
+
+/// Open tasks `task` is waiting on
+fn blockers<'a>(tasks: &'a [Task], task: &'a Task) -> impl Iterator<Item = &'a Task> {
+    tasks
+        .iter()
+        .filter(move |t| t.is_open() && task.depends_on.contains(&t.id))
+}
+
+/// True when `from` waits on `target`, directly or through other tasks
+fn depends_transitively(tasks: &[Task], from: u32, target: u32) -> bool {
+    let mut seen = vec![from];
+    let mut pending = vec![from];
+    while let Some(id) = pending.pop() {
+        let Some(task) = tasks.iter().find(|t| t.id == id) else {
+            continue;
+        };
+        for &dep in &task.depends_on {
+            if dep == target {
+                return true;
+            }
+            if !seen.contains(&dep) {
+                seen.push(dep);
+                pending.push(dep);
+            }
+        }
+    }
+    false
+}
+
+/// IDs of every subtask below `id`, at any depth
+fn descendants(tasks: &[Task], id: u32) -> Vec<u32> {
+    let mut found: Vec<u32> = Vec::new();
+    let mut pending = vec![id];
+    while let Some(parent) = pending.pop() {
+        for task in tasks.iter().filter(|t| t.parent_id == Some(parent)) {
+            // Guards against cycles in hand-edited data
+            if task.id != id && !found.contains(&task.id) {
+                found.push(task.id);
+                pending.push(task.id);
+            }
+        }
+    }
+    found
+}
+
+// Drop repeated entries, keeping the first occurrence
+fn dedup<T: PartialEq>(items: Vec<T>) -> Vec<T> {
+    let mut unique: Vec<T> = Vec::with_capacity(items.len());
+    for item in items {
+        if !unique.contains(&item) {
+            unique.push(item);
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory_store::MemoryStorage;
+    use chrono::NaiveDate;
+
+    fn service() -> TaskService<MemoryStorage> {
+        TaskService::new(MemoryStorage::new())
+    }
+
+    fn add(service: &TaskService<MemoryStorage>, title: &str) -> Task {
+        service
+            .create_task(NewTask {
+                title: title.to_string(),
+                ..NewTask::default()
+            })
+            .unwrap()
+    }
+
+    fn set_status(
+        service: &TaskService<MemoryStorage>,
+        ids: &[u32],
+        status: Status,
+    ) -> Vec<UpdateOutcome> {
+        let changes = TaskChanges {
+            status: Some(status),
+            ..TaskChanges::default()
+        };
+        service.update_tasks(ids, changes).unwrap()
+    }
+
+    fn due(y: i32, m: u32, d: u32) -> DateTime<Local> {
+        let day = NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        Local
+            .from_local_datetime(&day.and_time(NaiveTime::MIN))
+            .earliest()
+            .unwrap()
+    }
+
+    #[test]
+    fn create_numbers_tasks_and_reports_missing_ones() {
+        let service = service();
+        assert_eq!(add(&service, "a").id, 1);
+        assert_eq!(add(&service, "b").id, 2);
+        assert_eq!(service.get_task(2).unwrap().title, "b");
+        assert!(matches!(service.get_task(9), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn subtasks_need_an_existing_parent() {
+        let service = service();
+        let orphan = service.create_task(NewTask {
+            title: "c".to_string(),
+            parent_id: Some(9),
+            ..NewTask::default()
+        });
+        assert!(matches!(orphan, Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn closing_a_parent_needs_cascade_for_open_subtasks() {
+        let service = service();
+        add(&service, "parent");
+        service
+            .create_task(NewTask {
+                title: "child".to_string(),
+                parent_id: Some(1),
+                ..NewTask::default()
+            })
+            .unwrap();
+
+        let refused = service.update_tasks(
+            &[1],
+            TaskChanges {
+                status: Some(Status::Done),
+                ..TaskChanges::default()
+            },
+        );
+        assert!(matches!(refused, Err(AppError::Conflict(_))));
+        assert_eq!(service.get_task(1).unwrap().status, Status::Todo);
+
+        service
+            .update_tasks(
+                &[1],
+                TaskChanges {
+                    status: Some(Status::Done),
+                    cascade: true,
+                    ..TaskChanges::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(service.get_task(2).unwrap().status, Status::Done);
+    }
+
+    #[test]
+    fn a_rejected_status_change_leaves_every_task_untouched() {
+        let service = service();
+        add(&service, "a");
+        add(&service, "b");
+        set_status(&service, &[1], Status::Done);
+
+        let result = service.update_tasks(
+            &[2, 1],
+            TaskChanges {
+                status: Some(Status::Done),
+                ..TaskChanges::default()
+            },
+        );
+        assert!(matches!(result, Err(AppError::Validation(_))));
+        assert_eq!(service.get_task(2).unwrap().status, Status::Todo);
+    }
+
+    #[test]
+    fn monthly_series_keep_their_day_of_month() {
+        let service = service();
+        service
+            .create_task(NewTask {
+                title: "rent".to_string(),
+                due: Some(due(2099, 1, 31)),
+                recurrence: Some("monthly".parse().unwrap()),
+                ..NewTask::default()
+            })
+            .unwrap();
+
+        let mut dues = Vec::new();
+        let mut id = 1;
+        for _ in 0..3 {
+            let outcomes = set_status(&service, &[id], Status::Done);
+            let next = outcomes[0].spawned.clone().unwrap();
+            assert_eq!(next.series(), 1);
+            dues.push(next.due.unwrap().date_naive());
+            id = next.id;
+        }
+        assert_eq!(
+            dues,
+            [
+                NaiveDate::from_ymd_opt(2099, 2, 28).unwrap(),
+                NaiveDate::from_ymd_opt(2099, 3, 31).unwrap(),
+                NaiveDate::from_ymd_opt(2099, 4, 30).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_completion_spawns_the_next_occurrence() {
+        let service = service();
+        service
+            .create_task(NewTask {
+                title: "water plants".to_string(),
+                due: Some(due(2099, 1, 1)),
+                recurrence: Some("every 3 days".parse().unwrap()),
+                ..NewTask::default()
+            })
+            .unwrap();
+
+        let outcomes = set_status(&service, &[1], Status::Cancelled);
+        assert!(outcomes[0].spawned.is_none());
+        assert_eq!(service.list_tasks(&Filter::default()).unwrap().len(), 1);
+
+        set_status(&service, &[1], Status::Todo);
+        let outcomes = set_status(&service, &[1], Status::Done);
+        let next = outcomes[0].spawned.as_ref().unwrap();
+        assert_eq!(next.due, Some(due(2099, 1, 4)));
+    }
+
+    #[test]
+    fn dependencies_must_exist() {
+        let service = service();
+        add(&service, "a");
+        let waiting = service.create_task(NewTask {
+            title: "c".to_string(),
+            depends_on: vec![1, 7],
+            ..NewTask::default()
+        });
+        assert!(matches!(waiting, Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn completing_a_dependency_unblocks_waiting_tasks() {
+        let service = service();
+        add(&service, "design");
+        service
+            .create_task(NewTask {
+                title: "build".to_string(),
+                depends_on: vec![1],
+                ..NewTask::default()
+            })
+            .unwrap();
+
+        let cycle = service.update_tasks(
+            &[1],
+            TaskChanges {
+                add_depends_on: vec![2],
+                ..TaskChanges::default()
+            },
+        );
+        assert!(matches!(cycle, Err(AppError::Validation(_))));
+
+        let outcomes = set_status(&service, &[1], Status::Done);
+        let unblocked: Vec<u32> = outcomes[0].unblocked.iter().map(|t| t.id).collect();
+        assert_eq!(unblocked, [2]);
+    }
+
+    #[test]
+    fn deleting_a_task_drops_it_from_dependencies() {
+        let service = service();
+        add(&service, "a");
+        service
+            .create_task(NewTask {
+                title: "b".to_string(),
+                depends_on: vec![1],
+                ..NewTask::default()
+            })
+            .unwrap();
+
+        assert_eq!(service.delete_task(1, false).unwrap(), [1]);
+        assert!(service.get_task(2).unwrap().depends_on.is_empty());
+    }
+
+    #[test]
+    fn one_timer_runs_at_a_time() {
+        let service = service();
+        add(&service, "a");
+        add(&service, "b");
+
+        assert!(matches!(service.stop_timer(), Err(AppError::NotFound(_))));
+        let first = service.start_timer(1).unwrap();
+        assert_eq!(first.task.status, Status::InProgress);
+        assert!(first.stopped.is_none());
+        assert!(matches!(service.start_timer(1), Err(AppError::Conflict(_))));
+
+        let second = service.start_timer(2).unwrap();
+        assert_eq!(second.stopped.map(|(task, _)| task.id), Some(1));
+        assert!(service.get_task(1).unwrap().running_timer().is_none());
+
+        let (stopped, _) = service.stop_timer().unwrap();
+        assert_eq!(stopped.id, 2);
+    }
+
+    #[test]
+    fn snoozing_needs_an_open_task_and_a_future_date() {
+        let service = service();
+        add(&service, "a");
+
+        let past = Local::now() - Duration::days(1);
+        assert!(matches!(
+            service.snooze(1, Some(past)),
+            Err(AppError::Validation(_))
+        ));
+
+        let later = Local::now() + Duration::days(2);
+        let snoozed = service.snooze(1, Some(later)).unwrap();
+        assert!(snoozed.is_snoozed(Local::now()));
+        assert!(
+            service
+                .next_tasks(&UrgencyWeights::default(), 10)
+                .unwrap()
+                .is_empty()
+        );
+
+        set_status(&service, &[1], Status::Done);
+        assert!(matches!(
+            service.snooze(1, None),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn a_batch_update_is_written_once() {
+        let storage = MemoryStorage::new();
+        let service = TaskService::new(storage.clone());
+        add(&service, "a");
+        add(&service, "b");
+        service
+            .create_task(NewTask {
+                title: "c".to_string(),
+                due: Some(due(2099, 1, 1)),
+                recurrence: Some("daily".parse().unwrap()),
+                ..NewTask::default()
+            })
+            .unwrap();
+
+        let before = storage.task_writes();
+        let outcomes = set_status(&service, &[1, 2, 3], Status::Done);
+        assert_eq!(storage.task_writes(), before + 1);
+        assert_eq!(outcomes.len(), 3);
+        let tasks = storage.load_tasks().unwrap();
+        assert_eq!(tasks.len(), 4);
+        assert!(tasks[..3].iter().all(|t| t.status == Status::Done));
+    }
+
+    #[test]
+    fn due_dates_and_priorities_can_be_removed() {
+        let service = service();
+        service
+            .create_task(NewTask {
+                title: "a".to_string(),
+                priority: Some(Priority::High),
+                due: Some(due(2099, 1, 31)),
+                ..NewTask::default()
+            })
+            .unwrap();
+        service
+            .update_tasks(
+                &[1],
+                TaskChanges {
+                    clear_priority: true,
+                    clear_due: true,
+                    ..TaskChanges::default()
+                },
+            )
+            .unwrap();
+        let task = service.get_task(1).unwrap();
+        assert!(task.priority.is_none());
+        assert!(task.due.is_none());
     }
 }
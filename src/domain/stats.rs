@@ -0,0 +1,206 @@
+use crate::domain::models::{Status, Task};
+use chrono::{DateTime, Datelike, Duration, Local};
+use std::collections::BTreeMap;
+
+/// A task with an estimate and the time tracked on it so far
+#[derive(Debug)]
+pub struct EstimateRow {
+    pub task: Task,
+    pub estimate: Duration,
+    pub actual: Duration,
+}
+
+/// Estimated and tracked time summed over a group of done tasks
+#[derive(Debug)]
+pub struct Accuracy {
+    pub label: String,
+    pub tasks: usize,
+    pub estimate: Duration,
+    pub actual: Duration,
+}
+
+impl Accuracy {
+    fn new(label: String) -> Self {
+        Self {
+            label,
+            tasks: 0,
+            estimate: Duration::zero(),
+            actual: Duration::zero(),
+        }
+    }
+
+    fn add(&mut self, row: &EstimateRow) {
+        self.tasks += 1;
+        self.estimate += row.estimate;
+        self.actual += row.actual;
+    }
+
+    /// Actual over estimated time, above 1 when work took longer than planned
+    pub fn ratio(&self) -> Option<f64> {
+        ratio(self.estimate, self.actual)
+    }
+}
+
+/// Estimates compared with tracked time, see [`estimate_report`]
+#[derive(Debug)]
+pub struct EstimateReport {
+    /// Every estimated task, by ID
+    pub tasks: Vec<EstimateRow>,
+    /// Done tasks per project, by name
+    pub projects: Vec<Accuracy>,
+    /// Done tasks per ISO week of completion, oldest first
+    pub trend: Vec<Accuracy>,
+    /// All done tasks
+    pub overall: Accuracy,
+}
+
+/// Compare the estimates of `tasks` with their tracked time, pomodoro
+/// estimates counting `pomodoro` each. Only done tasks count towards the
+/// accuracy figures, unfinished or cancelled work would skew them. The
+/// trend keeps the last `weeks` weeks with completions.
+pub fn estimate_report(
+    tasks: &[Task],
+    pomodoro: Duration,
+    weeks: usize,
+    now: DateTime<Local>,
+) -> EstimateReport {
+    let mut rows: Vec<EstimateRow> = tasks
+        .iter()
+        .filter(|t| t.status != Status::Cancelled)
+        .filter_map(|t| {
+            Some(EstimateRow {
+                estimate: t.estimate?.duration(pomodoro),
+                actual: t.tracked(None, now),
+                task: t.clone(),
+            })
+        })
+        .collect();
+    rows.sort_by_key(|r| r.task.id);
+
+    let mut projects: BTreeMap<String, Accuracy> = BTreeMap::new();
+    let mut trend: BTreeMap<(i32, u32), Accuracy> = BTreeMap::new();
+    let mut overall = Accuracy::new("overall".to_string());
+    for row in &rows {
+        let Some(done_at) = row.task.done_at() else {
+            continue;
+        };
+        let project = row.task.project.as_deref().unwrap_or("(none)");
+        projects
+            .entry(project.to_string())
+            .or_insert_with(|| Accuracy::new(project.to_string()))
+            .add(row);
+        let week = done_at.iso_week();
+        trend
+            .entry((week.year(), week.week()))
+            .or_insert_with(|| Accuracy::new(format!("{}-W{:02}", week.year(), week.week())))
+            .add(row);
+        overall.add(row);
+    }
+
+    let mut trend: Vec<Accuracy> = trend.into_values().collect();
+    trend.drain(..trend.len().saturating_sub(weeks));
+    EstimateReport {
+        tasks: rows,
+        projects: projects.into_values().collect(),
+        trend,
+        overall,
+    }
+}
+
+/// Actual over estimated time, `None` without an estimate to divide by
+pub fn ratio(estimate: Duration, actual: Duration) -> Option<f64> {
+    let estimate = estimate.num_seconds();
+    (estimate > 0).then(|| actual.num_seconds() as f64 / estimate as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    // Tracked for `minutes` on 2026-10-01, and done on `done` at noon
+    fn task(id: u32, estimate: &str, status: &str, done: &str, minutes: i64) -> Task {
+        let start = Local.with_ymd_and_hms(2026, 10, 1, 9, 0, 0).unwrap();
+        let mut task: Task = serde_json::from_value(json!({
+            "id": id,
+            "title": format!("task {}", id),
+            "description": null,
+            "created_at": "2026-09-01T08:00:00Z",
+            "status": status,
+            "history": [{ "status": status, "at": format!("{}T12:00:00Z", done) }],
+            "time_log": [{
+                "start": start,
+                "end": start + Duration::minutes(minutes)
+            }]
+        }))
+        .unwrap();
+        task.estimate = (!estimate.is_empty()).then(|| estimate.parse().unwrap());
+        task
+    }
+
+    fn in_project(mut task: Task, project: &str) -> Task {
+        task.project = Some(project.to_string());
+        task
+    }
+
+    fn report(weeks: usize) -> EstimateReport {
+        let tasks = [
+            in_project(task(1, "1h", "done", "2026-10-06", 90), "work"),
+            in_project(task(2, "2p", "done", "2026-10-13", 50), "work"),
+            task(3, "30m", "done", "2026-09-29", 15),
+            task(4, "1h", "todo", "2026-10-01", 10),
+            task(5, "1h", "cancelled", "2026-10-02", 20),
+            task(6, "", "done", "2026-10-13", 40),
+        ];
+        let now = Local.with_ymd_and_hms(2026, 10, 14, 10, 0, 0).unwrap();
+        estimate_report(&tasks, Duration::minutes(25), weeks, now)
+    }
+
+    fn minutes(accuracy: &Accuracy) -> (usize, i64, i64) {
+        (
+            accuracy.tasks,
+            accuracy.estimate.num_minutes(),
+            accuracy.actual.num_minutes(),
+        )
+    }
+
+    #[test]
+    fn rows_skip_cancelled_and_unestimated_tasks() {
+        let report = report(8);
+        let ids: Vec<u32> = report.tasks.iter().map(|r| r.task.id).collect();
+        assert_eq!(ids, [1, 2, 3, 4]);
+        assert_eq!(report.tasks[1].estimate, Duration::minutes(50));
+    }
+
+    #[test]
+    fn only_done_tasks_count_towards_accuracy() {
+        let report = report(8);
+        assert_eq!(minutes(&report.overall), (3, 140, 155));
+        let projects: Vec<(&str, (usize, i64, i64))> = report
+            .projects
+            .iter()
+            .map(|p| (p.label.as_str(), minutes(p)))
+            .collect();
+        assert_eq!(projects, [("(none)", (1, 30, 15)), ("work", (2, 110, 140))]);
+        assert_eq!(report.overall.ratio(), Some(155.0 / 140.0));
+    }
+
+    #[test]
+    fn the_trend_keeps_the_latest_weeks() {
+        let labels =
+            |weeks| -> Vec<String> { report(weeks).trend.into_iter().map(|a| a.label).collect() };
+        assert_eq!(labels(8), ["2026-W40", "2026-W41", "2026-W42"]);
+        assert_eq!(labels(2), ["2026-W41", "2026-W42"]);
+        assert!(labels(0).is_empty());
+    }
+
+    #[test]
+    fn ratios_need_an_estimate() {
+        assert_eq!(ratio(Duration::zero(), Duration::minutes(5)), None);
+        assert_eq!(
+            ratio(Duration::minutes(40), Duration::minutes(30)),
+            Some(0.75)
+        );
+    }
+}
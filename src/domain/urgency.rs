@@ -0,0 +1,211 @@
+use crate::domain::models::{Priority, Task};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// Coefficients of the urgency score, the `[urgency]` section of the config.
+///
+/// Each factor is scaled to 0..=1 and multiplied by its weight; the score is
+/// the sum. Negative weights push tasks down instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrgencyWeights {
+    /// Due date: full weight a week overdue, a fifth of it two weeks out
+    pub due: f64,
+    pub priority_high: f64,
+    pub priority_medium: f64,
+    pub priority_low: f64,
+    /// Age, reaching full weight after a year
+    pub age: f64,
+    /// Other open tasks wait on this one
+    pub blocking: f64,
+    /// Any tags, full weight from three tags up
+    pub tags: f64,
+}
+
+impl Default for UrgencyWeights {
+    fn default() -> Self {
+        Self {
+            due: 12.0,
+            priority_high: 6.0,
+            priority_medium: 3.9,
+            priority_low: 1.8,
+            age: 2.0,
+            blocking: 8.0,
+            tags: 1.0,
+        }
+    }
+}
+
+/// An urgency score and the terms it adds up from
+#[derive(Debug, Clone)]
+pub struct Urgency {
+    pub score: f64,
+    /// Non-zero `(factor, contribution)` terms, largest first
+    pub parts: Vec<(&'static str, f64)>,
+}
+
+/// Score `task`. `tasks` is the whole task list, used to tell whether
+/// anything waits on it.
+pub fn urgency(
+    task: &Task,
+    tasks: &[Task],
+    weights: &UrgencyWeights,
+    now: DateTime<Local>,
+) -> Urgency {
+    let priority = match task.priority {
+        Some(Priority::High) => weights.priority_high,
+        Some(Priority::Medium) => weights.priority_medium,
+        Some(Priority::Low) => weights.priority_low,
+        None => 0.0,
+    };
+    let blocking = tasks
+        .iter()
+        .any(|t| t.is_open() && t.depends_on.contains(&task.id));
+
+    let mut parts = vec![
+        ("due", weights.due * due_factor(task, now)),
+        ("priority", priority),
+        ("age", weights.age * age_factor(task, now)),
+        ("blocking", if blocking { weights.blocking } else { 0.0 }),
+        ("tags", weights.tags * tags_factor(task)),
+    ];
+    parts.retain(|(_, value)| *value != 0.0);
+    parts.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+
+    Urgency {
+        score: parts.iter().fold(0.0, |sum, (_, value)| sum + value),
+        parts,
+    }
+}
+
+// 1.0 from a week overdue, falling linearly to 0.2 two weeks ahead
+fn due_factor(task: &Task, now: DateTime<Local>) -> f64 {
+    let Some(due) = task.due else {
+        return 0.0;
+    };
+    let days = (due - now).num_minutes() as f64 / (24.0 * 60.0);
+    if days <= -7.0 {
+        1.0
+    } else if days >= 14.0 {
+        0.2
+    } else {
+        // -7 days -> 1.0, +14 days -> 0.2
+        1.0 - (days + 7.0) * 0.8 / 21.0
+    }
+}
+
+fn age_factor(task: &Task, now: DateTime<Local>) -> f64 {
+    let days = (now - task.created_at).num_days().max(0) as f64;
+    (days / 365.0).min(1.0)
+}
+
+fn tags_factor(task: &Task) -> f64 {
+    match task.tags.len() {
+        0 => 0.0,
+        1 => 0.8,
+        2 => 0.9,
+        _ => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::Status;
+    use chrono::{Duration, TimeZone};
+    use serde_json::json;
+
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 14, 10, 0, 0).unwrap()
+    }
+
+    // A todo task created `age` days before `now`
+    fn task(id: u32, age: i64) -> Task {
+        serde_json::from_value(json!({
+            "id": id,
+            "title": "t",
+            "description": null,
+            "created_at": now() - Duration::days(age),
+            "status": "todo"
+        }))
+        .unwrap()
+    }
+
+    fn due_in(days: i64) -> Task {
+        let mut task = task(1, 0);
+        task.due = Some(now() + Duration::days(days));
+        task
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn the_due_factor_rises_from_two_weeks_out_to_a_week_overdue() {
+        assert_eq!(due_factor(&task(1, 0), now()), 0.0);
+        assert_eq!(due_factor(&due_in(30), now()), 0.2);
+        assert_eq!(due_factor(&due_in(14), now()), 0.2);
+        assert!(close(
+            due_factor(&due_in(7), now()),
+            1.0 - 14.0 * 0.8 / 21.0
+        ));
+        assert!(close(due_factor(&due_in(0), now()), 1.0 - 7.0 * 0.8 / 21.0));
+        assert_eq!(due_factor(&due_in(-7), now()), 1.0);
+        assert_eq!(due_factor(&due_in(-100), now()), 1.0);
+    }
+
+    #[test]
+    fn the_age_factor_grows_over_a_year() {
+        assert_eq!(age_factor(&task(1, 0), now()), 0.0);
+        assert!(close(age_factor(&task(1, 73), now()), 0.2));
+        assert_eq!(age_factor(&task(1, 365), now()), 1.0);
+        assert_eq!(age_factor(&task(1, 800), now()), 1.0);
+        // A clock set back doesn't make a task younger than new
+        assert_eq!(age_factor(&task(1, -3), now()), 0.0);
+    }
+
+    #[test]
+    fn parts_are_summed_and_sorted_by_size() {
+        let mut urgent = due_in(-7);
+        urgent.created_at = now() - Duration::days(365);
+        urgent.priority = Some(Priority::High);
+        urgent.tags = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut waiting = task(2, 0);
+        waiting.depends_on = vec![1];
+        let tasks = [urgent.clone(), waiting];
+
+        let result = urgency(&urgent, &tasks, &UrgencyWeights::default(), now());
+        let names: Vec<&str> = result.parts.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["due", "blocking", "priority", "age", "tags"]);
+        assert!(close(result.score, 12.0 + 8.0 + 6.0 + 2.0 + 1.0));
+    }
+
+    #[test]
+    fn zero_terms_are_dropped_and_negative_weights_sort_by_size() {
+        let fresh = task(1, 0);
+        let result = urgency(&fresh, &[], &UrgencyWeights::default(), now());
+        assert!(result.parts.is_empty());
+        assert_eq!(result.score, 0.0);
+
+        let mut old = task(1, 365);
+        old.priority = Some(Priority::Medium);
+        let weights = UrgencyWeights {
+            age: -5.0,
+            ..UrgencyWeights::default()
+        };
+        let result = urgency(&old, &[], &weights, now());
+        assert_eq!(result.parts, [("age", -5.0), ("priority", 3.9)]);
+        assert!(close(result.score, -1.1));
+    }
+
+    #[test]
+    fn only_open_tasks_make_a_task_blocking() {
+        let blocker = task(1, 0);
+        let mut done = task(2, 0);
+        done.depends_on = vec![1];
+        done.status = Status::Done;
+        let result = urgency(&blocker, &[done], &UrgencyWeights::default(), now());
+        assert!(result.parts.iter().all(|(name, _)| *name != "blocking"));
+    }
+}
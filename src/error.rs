@@ -0,0 +1,48 @@
+use std::fmt;
+
+/// Errors surfaced by the services and storage backends
+#[derive(Debug)]
+pub enum AppError {
+    /// The requested item doesn't exist
+    NotFound(String),
+    /// User input was rejected
+    Validation(String),
+    /// Reading or writing the underlying storage failed
+    Storage(String),
+    /// Stored data couldn't be understood
+    Parse(String),
+    /// Another process holds the data, or the change clashes with existing data
+    Conflict(String),
+    /// A pomodoro command needs a running session
+    NoActiveSession,
+}
+
+impl AppError {
+    /// Process exit code for this error. 1 is left for unexpected failures
+    /// and 2 for usage errors reported by clap.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AppError::NotFound(_) => 3,
+            AppError::Validation(_) => 4,
+            AppError::Storage(_) => 5,
+            AppError::Parse(_) => 6,
+            AppError::Conflict(_) => 7,
+            AppError::NoActiveSession => 8,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound(msg)
+            | AppError::Validation(msg)
+            | AppError::Storage(msg)
+            | AppError::Parse(msg)
+            | AppError::Conflict(msg) => write!(f, "{}", msg),
+            AppError::NoActiveSession => write!(f, "No active pomodoro session"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
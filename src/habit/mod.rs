@@ -1,2 +0,0 @@
-pub mod model;
-pub mod service;
\ No newline at end of file
@@ -1,173 +1,744 @@
 mod cli;
+mod config;
 mod domain;
+mod error;
 mod storage;
 
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
 use clap::Parser;
 use cli::args::{
-    Cli, CrudActions, EntityCommands, HabitCommand, PomodoroActions, PomodoroCommand, TaskCommand,
+    Backend, Cli, ConfigActions, ConfigCommand, EntityCommands, HabitActions, HabitCommand,
+    NextArgs, PomodoroActions, PomodoroCommand, ProjectActions, ProjectCommand, ReportBy,
+    StatsActions, StatsCommand, StorageActions, StorageCommand, TaskActions, TaskCommand,
+    TaskStatusArgs, TimeActions, TimeCommand,
 };
-use domain::services::{HabitService, PomodoroService, TaskService};
+use config::{Config, StorageBackend};
+use crossterm::style::{Color, Stylize};
+use domain::dates;
+use domain::filter::Filter;
+use domain::models::{DueState, Habit, HabitState, Measure, Status, Task};
+use domain::services::{
+    HabitChanges, HabitService, NewTask, PomodoroService, ReportGroup, TaskChanges, TaskService,
+    TaskView, UpdateOutcome,
+};
+use domain::stats::{self, Accuracy};
+use error::AppError;
+use std::io::{self, IsTerminal, Write};
+use std::process;
+use storage::Storage;
 use storage::json_store::JsonStorage;
+use storage::schema::CURRENT_VERSION;
+use storage::sqlite_store::SqliteStorage;
 
 fn main() {
     let cli = Cli::parse();
 
+    if let Err(e) = start(cli) {
+        eprintln!("Error: {}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+fn start(cli: Cli) -> Result<(), AppError> {
+    // Config and date commands never touch the data, and `config` has to
+    // work with a broken config file to be able to fix it
+    let command = match cli.command {
+        EntityCommands::Config(cmd) => return handle_config(cmd),
+        EntityCommands::CalcDate { expr } => return handle_calc_date(&expr.join(" ")),
+        command => command,
+    };
+    let config = Config::load()?;
+
+    let data_dir = config::resolve_data_dir(cli.data_dir, &config);
+
+    // Older releases kept everything in ./data, pick up a copy once
+    if data_dir.is_default && storage::adopt_legacy_dir(config::LEGACY_DATA_DIR, &data_dir.path)? {
+        eprintln!(
+            "Copied existing data from {} to {}; the originals were left in place",
+            config::LEGACY_DATA_DIR,
+            data_dir.path
+        );
+    }
+
     // Initialize storage
-    let storage = JsonStorage::new("./data");
+    let dir = data_dir.path.as_str();
+    let backend = match cli.backend {
+        Some(Backend::Json) => StorageBackend::Json,
+        Some(Backend::Sqlite) => StorageBackend::Sqlite,
+        None => config.storage.backend,
+    };
+    match backend {
+        StorageBackend::Json => run(command, JsonStorage::new(dir)?, dir, &config),
+        StorageBackend::Sqlite => run(command, SqliteStorage::new(dir)?, dir, &config),
+    }
+}
 
+fn run<S: Storage + Clone>(
+    command: EntityCommands,
+    storage: S,
+    data_dir: &str,
+    config: &Config,
+) -> Result<(), AppError> {
     // Initialize services
-    // We clone storage because it's just a wrapper around a Path String, so it's cheap
+    // Cloning storage is cheap: it's either a path or a shared handle
     let task_service = TaskService::new(storage.clone());
     let habit_service = HabitService::new(storage.clone());
-    let pomodoro_service = PomodoroService::new(storage);
+    let pomodoro_service = PomodoroService::new(storage.clone());
 
-    match cli.command {
-        EntityCommands::Task(cmd) => handle_task(cmd, &task_service),
-        EntityCommands::Habit(cmd) => handle_habit(cmd, &habit_service),
-        EntityCommands::Pomodoro(cmd) => handle_pomodoro(cmd, &pomodoro_service),
+    match command {
+        EntityCommands::Task(cmd) => handle_task(cmd, &task_service, config),
+        EntityCommands::Habit(cmd) => handle_habit(cmd, &habit_service, config),
+        EntityCommands::Pomodoro(cmd) => handle_pomodoro(cmd, &pomodoro_service, config),
+        EntityCommands::Project(cmd) => handle_project(cmd, &task_service),
+        EntityCommands::Next(args) => handle_next(args, &task_service, config),
+        EntityCommands::Time(cmd) => handle_time(cmd, &task_service),
+        EntityCommands::Stats(cmd) => handle_stats(cmd, &task_service, config),
+        EntityCommands::Storage(cmd) => handle_storage(cmd, &storage, data_dir, config),
+        EntityCommands::Config(_) | EntityCommands::CalcDate { .. } => {
+            unreachable!("handled before the storage is opened")
+        }
     }
 }
 
 /// Handle Task commands
-fn handle_task(cmd: TaskCommand, service: &TaskService) {
+fn handle_task(
+    cmd: TaskCommand,
+    service: &TaskService<impl Storage>,
+    config: &Config,
+) -> Result<(), AppError> {
     match cmd.action {
-        CrudActions::Enter(args) => {
-            if let Some(title) = args.title {
-                match service.create_task(title, args.description) {
-                    Ok(task) => println!("Task created: {} (ID: {})", task.title, task.id),
-                    Err(e) => eprintln!("Error creating task: {}", e),
+        TaskActions::Enter(args) => {
+            let title = args.title.ok_or_else(|| {
+                AppError::Validation("Title is required for creating a task.".to_string())
+            })?;
+            let task = service.create_task(NewTask {
+                title,
+                description: args.description,
+                priority: args.priority,
+                due: args.due,
+                recurrence: args.recur,
+                tags: args.tag,
+                project: args.project,
+                parent_id: args.parent,
+                depends_on: args.depends_on,
+                estimate: args.estimate,
+            })?;
+            println!("Task created: {} (ID: {})", task.title, task.id);
+            if let Some(rule) = &task.recurrence {
+                println!("Repeats {}.", rule);
+            }
+        }
+        TaskActions::Delete(args) => {
+            let ids = match args.id {
+                Some(id) => service.delete_task(id, args.cascade)?,
+                None => {
+                    let ids = select_tasks(service, &args.filter, "Delete", args.yes, |_| Ok(()))?;
+                    service.delete_tasks(&ids, args.cascade)?
                 }
-            } else {
-                eprintln!("Error: Title is required for creating a task.");
+            };
+            for id in ids {
+                println!("Task {} deleted.", id);
             }
         }
-        CrudActions::Delete(args) => match service.delete_task(args.id) {
-            Ok(_) => println!("Task {} deleted.", args.id),
-            Err(e) => eprintln!("Error deleting task: {}", e),
-        },
-        CrudActions::Read => match service.list_tasks() {
-            Ok(tasks) => {
-                if tasks.is_empty() {
-                    println!("No tasks found.");
-                } else {
+        TaskActions::Read(args) => {
+            // The flags are shorthands for filter terms
+            let mut words = args.filter;
+            words.extend(args.tag.iter().map(|tag| match tag.strip_prefix('-') {
+                Some(excluded) => format!("-{}", excluded),
+                None => format!("+{}", tag),
+            }));
+            words.extend(args.project.map(|p| format!("project:{}", p)));
+            let filter = Filter::parse(&words, Local::now()).map_err(AppError::Validation)?;
+
+            let view = if args.ready {
+                TaskView::Ready
+            } else if args.blocked {
+                TaskView::Blocked
+            } else {
+                TaskView::All
+            };
+            let rows = service.task_tree(&filter, view, args.all)?;
+            let now = Local::now();
+            if rows.is_empty() {
+                println!("No tasks found.");
+            } else {
+                let (tw, dw) = (config.output.title_width, config.output.description_width);
+                println!(
+                    "{:<5} {:<tw$} {:<10} {:<4} {:<24} {:<15} {:<dw$} {:<4}",
+                    "ID", "Title", "Status", "Pri", "Due", "Project", "Description", "Tags"
+                );
+                println!("{}", "-".repeat(5 + tw + 10 + 4 + 24 + 15 + dw + 10));
+                for row in rows {
+                    let task = &row.task;
+                    // Subtasks are indented below their parent, which shows progress
+                    let title = format!("{}{}", "  ".repeat(row.depth), task.title);
+                    let mut status = task.status.marker().to_string();
+                    if let Some((done, total)) = row.progress {
+                        status.push_str(&format!(" {}/{}", done, total));
+                    }
+                    // Just back from snoozing
+                    if task.is_new(now) {
+                        status.push_str(" new");
+                    } else if task.is_snoozed(now) {
+                        status.push_str(" zz");
+                    }
+                    let priority = task.priority.map(|p| p.to_string()).unwrap_or_default();
+                    let tags: Vec<String> = task.tags.iter().map(|t| format!("+{}", t)).collect();
                     println!(
-                        "{:<5} {:<20} {:<10} {:<30}",
-                        "ID", "Title", "Status", "Description"
+                        "{:<5} {:<tw$} {:<10} {:<4} {} {:<15} {:<dw$} {}",
+                        task.id,
+                        truncate(&title, tw),
+                        status,
+                        priority,
+                        due_cell(task, now, 24),
+                        truncate(task.project.as_deref().unwrap_or_default(), 15),
+                        truncate(task.description.as_deref().unwrap_or_default(), dw),
+                        tags.join(" ")
                     );
-                    println!("{}", "-".repeat(65));
-                    for task in tasks {
-                        let status = if task.completed { "[x]" } else { "[ ]" };
-                        println!(
-                            "{:<5} {:<20} {:<10} {:<30}",
-                            task.id,
-                            truncate(&task.title, 20),
-                            status,
-                            truncate(&task.description.unwrap_or_default(), 30)
-                        );
+                    if view == TaskView::Blocked {
+                        let waits: Vec<String> = row
+                            .blocked_by
+                            .iter()
+                            .map(|t| format!("#{} {}", t.id, t.title))
+                            .collect();
+                        println!("{:<5} waits on: {}", "", waits.join(", "));
                     }
                 }
             }
-            Err(e) => eprintln!("Error reading tasks: {}", e),
-        },
-        CrudActions::Update(args) => {
-            match service.update_task(args.id, args.title, args.description, Some(args.completed)) {
-                Ok(task) => println!("Task {} updated.", task.id),
-                Err(e) => eprintln!("Error updating task: {}", e),
+            if !args.all {
+                let snoozed = service
+                    .list_tasks(&filter)?
+                    .iter()
+                    .filter(|t| t.is_snoozed(now))
+                    .count();
+                if snoozed > 0 {
+                    println!("({} snoozed, use --all to include them)", snoozed);
+                }
+            }
+        }
+        TaskActions::Update(args) => {
+            let changes = TaskChanges {
+                title: args.title,
+                description: args.description,
+                status: args.status,
+                priority: args.priority,
+                clear_priority: args.no_priority,
+                due: args.due,
+                clear_due: args.no_due,
+                recurrence: args.recur,
+                clear_recurrence: args.no_recur,
+                add_tags: args.tag,
+                remove_tags: args.remove_tag,
+                project: args.project,
+                clear_project: args.no_project,
+                parent_id: args.parent,
+                clear_parent: args.no_parent,
+                cascade: args.cascade,
+                add_depends_on: args.depends_on,
+                remove_depends_on: args.remove_depends_on,
+                estimate: args.estimate,
+                clear_estimate: args.no_estimate,
+            };
+            let outcomes = match args.id {
+                Some(id) => service.update_tasks(&[id], changes)?,
+                None => {
+                    let status = changes.status;
+                    let ids = select_tasks(service, &args.filter, "Update", args.yes, |task| {
+                        status.map_or(Ok(()), |s| can_move(task, s))
+                    })?;
+                    service.update_tasks(&ids, changes)?
+                }
+            };
+            for outcome in &outcomes {
+                println!("Task {} updated.", outcome.task.id);
+            }
+            print_follow_ups(&outcomes);
+        }
+        // A single task also gets its timer started
+        TaskActions::Start(TaskStatusArgs { id: Some(id), .. }) => {
+            let started = service.start_timer(id)?;
+            if let Some((other, ran)) = &started.stopped {
+                println!(
+                    "Stopped the timer on task {} after {}.",
+                    other.id,
+                    format_duration(*ran)
+                );
+            }
+            println!(
+                "Task {} is now {}, timer started.",
+                started.task.id, started.task.status
+            );
+        }
+        TaskActions::Start(args) => change_status(service, args, Status::InProgress, "Start")?,
+        TaskActions::Stop => {
+            let (task, ran) = service.stop_timer()?;
+            println!(
+                "Stopped the timer on task {} after {} ({} in total).",
+                task.id,
+                format_duration(ran),
+                format_duration(task.tracked(None, Local::now()))
+            );
+        }
+        TaskActions::Done(args) => change_status(service, args, Status::Done, "Complete")?,
+        TaskActions::Cancel(args) => change_status(service, args, Status::Cancelled, "Cancel")?,
+        TaskActions::Reopen(args) => change_status(service, args, Status::Todo, "Reopen")?,
+        TaskActions::Annotate(args) => {
+            let task = service.annotate(args.id, &args.text.join(" "))?;
+            println!("Task {} annotated.", task.id);
+        }
+        TaskActions::Denotate(args) => {
+            let note = service.denotate(args.id, args.number)?;
+            println!("Removed note from task {}: {}", args.id, note.text);
+        }
+        TaskActions::Show(args) => show_task(&service.get_task(args.id)?),
+        TaskActions::Snooze(args) => {
+            let until = if args.clear {
+                None
+            } else {
+                Some(
+                    dates::parse_date(&args.until.join(" "), Local::now())
+                        .map_err(AppError::Validation)?,
+                )
+            };
+            let task = service.snooze(args.id, until)?;
+            match task.wait_until {
+                Some(until) => println!("Task {} snoozed until {}.", task.id, format_date(until)),
+                None => println!("Task {} is back.", task.id),
             }
         }
     }
+    Ok(())
+}
+
+// Detail view of a single task
+fn show_task(task: &Task) {
+    let stamp = |at: DateTime<Local>| at.format("%Y-%m-%d %H:%M").to_string();
+    let ids = |ids: &[u32]| {
+        ids.iter()
+            .map(|id| format!("#{}", id))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    println!("Task {}: {}", task.id, task.title);
+    println!("{:<12} {}", "Status", task.status);
+    if let Some(priority) = task.priority {
+        println!("{:<12} {}", "Priority", priority);
+    }
+    if let Some(due) = task.due {
+        println!("{:<12} {}", "Due", format_date(due));
+    }
+    if let Some(project) = &task.project {
+        println!("{:<12} {}", "Project", project);
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|t| format!("+{}", t)).collect();
+        println!("{:<12} {}", "Tags", tags.join(" "));
+    }
+    if let Some(parent) = task.parent_id {
+        println!("{:<12} #{}", "Parent", parent);
+    }
+    if !task.depends_on.is_empty() {
+        println!("{:<12} {}", "Depends on", ids(&task.depends_on));
+    }
+    if let Some(rule) = &task.recurrence {
+        println!("{:<12} {}", "Repeats", rule);
+    }
+    println!("{:<12} {}", "Created", stamp(task.created_at));
+    if let Some(until) = task.wait_until.filter(|_| task.is_snoozed(Local::now())) {
+        println!("{:<12} {}", "Snoozed", format_date(until));
+    }
+    if let Some(estimate) = task.estimate {
+        println!("{:<12} {}", "Estimate", estimate);
+    }
+    if !task.time_log.is_empty() {
+        let running = if task.running_timer().is_some() {
+            " (timer running)"
+        } else {
+            ""
+        };
+        println!(
+            "{:<12} {}{}",
+            "Tracked",
+            format_duration(task.tracked(None, Local::now())),
+            running
+        );
+    }
+
+    if let Some(description) = &task.description {
+        println!();
+        println!("{}", description);
+    }
+
+    if !task.annotations.is_empty() {
+        println!();
+        println!("Notes:");
+        for (n, note) in task.annotations.iter().enumerate() {
+            println!("{:>3}. {}  {}", n + 1, stamp(note.at), note.text);
+        }
+    }
+
+    if !task.history.is_empty() {
+        println!();
+        println!("History:");
+        for change in &task.history {
+            println!("     {}  {}", stamp(change.at), change.status);
+        }
+    }
+}
+
+// Shared by start/done/cancel/reopen
+fn change_status(
+    service: &TaskService<impl Storage>,
+    args: TaskStatusArgs,
+    status: Status,
+    verb: &str,
+) -> Result<(), AppError> {
+    let changes = TaskChanges {
+        status: Some(status),
+        cascade: args.cascade,
+        ..TaskChanges::default()
+    };
+    let outcomes = match args.id {
+        Some(id) => service.update_tasks(&[id], changes)?,
+        None => {
+            let ids = select_tasks(service, &args.filter, verb, args.yes, |task| {
+                can_move(task, status)
+            })?;
+            service.update_tasks(&ids, changes)?
+        }
+    };
+    for outcome in &outcomes {
+        println!("Task {} is now {}.", outcome.task.id, outcome.task.status);
+    }
+    print_follow_ups(&outcomes);
+    Ok(())
+}
+
+// Tasks unblocked or spawned by an update
+fn print_follow_ups(outcomes: &[UpdateOutcome]) {
+    for outcome in outcomes {
+        for task in &outcome.unblocked {
+            println!("Task {} is ready: {}", task.id, task.title);
+        }
+        if let Some(next) = &outcome.spawned {
+            println!(
+                "Next occurrence: {} (ID: {}, due {})",
+                next.title,
+                next.id,
+                next.due.map(format_date).unwrap_or_default()
+            );
+        }
+    }
+}
+
+// Whether a status change would be accepted, without making it
+fn can_move(task: &Task, status: Status) -> Result<(), String> {
+    task.clone().set_status(status, Local::now())
+}
+
+/// IDs of the tasks matching `terms`, after listing them and asking the
+/// user to confirm. Empty when the user declines. Matches `check` rejects
+/// are reported and left out, so one of them doesn't fail the whole batch.
+fn select_tasks(
+    service: &TaskService<impl Storage>,
+    terms: &[String],
+    verb: &str,
+    yes: bool,
+    check: impl Fn(&Task) -> Result<(), String>,
+) -> Result<Vec<u32>, AppError> {
+    let filter = Filter::parse(terms, Local::now()).map_err(AppError::Validation)?;
+    let matched = service.list_tasks(&filter)?;
+    if matched.is_empty() {
+        return Err(AppError::NotFound("No tasks match the filter".to_string()));
+    }
+    let mut tasks = Vec::with_capacity(matched.len());
+    for task in matched {
+        match check(&task) {
+            Ok(()) => tasks.push(task),
+            Err(reason) => println!("Skipping: {}", reason),
+        }
+    }
+    if tasks.is_empty() {
+        println!("Nothing changed.");
+        return Ok(Vec::new());
+    }
+
+    if !yes {
+        for task in &tasks {
+            println!("{:<5} {} {}", task.id, task.status.marker(), task.title);
+        }
+        if !confirm(&format!("{} {} task(s)?", verb, tasks.len()))? {
+            println!("Nothing changed.");
+            return Ok(Vec::new());
+        }
+    }
+    Ok(tasks.iter().map(|t| t.id).collect())
+}
+
+// Ask a yes/no question on stdin, anything but y/yes means no
+fn confirm(question: &str) -> Result<bool, AppError> {
+    print!("{} [y/N] ", question);
+    io::stdout()
+        .flush()
+        .map_err(|e| AppError::Storage(format!("Failed to write to stdout: {}", e)))?;
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| AppError::Storage(format!("Failed to read from stdin: {}", e)))?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// Date only when the time is midnight, date and time otherwise
+fn format_date(date: DateTime<Local>) -> String {
+    if date.time() == NaiveTime::MIN {
+        date.format("%Y-%m-%d").to_string()
+    } else {
+        date.format("%Y-%m-%d %H:%M").to_string()
+    }
+}
+
+// `1h 05m`, or `12m` under an hour
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+// Due date padded to `width`, flagged and colored when overdue or due today
+fn due_cell(task: &Task, now: DateTime<Local>, width: usize) -> String {
+    let Some(due) = task.due else {
+        return " ".repeat(width);
+    };
+    let date = format_date(due);
+    let (text, color) = match task.due_state(now) {
+        Some(DueState::Overdue) => (format!("{} overdue", date), Some(Color::Red)),
+        Some(DueState::Today) => (format!("{} today", date), Some(Color::Yellow)),
+        _ => (date, None),
+    };
+    let padded = format!("{:<width$}", text);
+    match color {
+        Some(color) if io::stdout().is_terminal() => padded.with(color).to_string(),
+        _ => padded,
+    }
 }
 
 /// Handle Habit commands
-fn handle_habit(cmd: HabitCommand, service: &HabitService) {
+fn handle_habit(
+    cmd: HabitCommand,
+    service: &HabitService<impl Storage>,
+    config: &Config,
+) -> Result<(), AppError> {
     match cmd.action {
-        CrudActions::Enter(args) => {
-            if let Some(title) = args.title {
-                let freq = args.frequency.unwrap_or_else(|| "daily".to_string());
-                match service.create_habit(title, args.description, freq) {
-                    Ok(habit) => println!("Habit created: {} (ID: {})", habit.name, habit.id),
-                    Err(e) => eprintln!("Error creating habit: {}", e),
-                }
-            } else {
-                eprintln!("Error: Name (title) is required for creating a habit.");
+        HabitActions::Enter(args) => {
+            let title = args.title.ok_or_else(|| {
+                AppError::Validation("Name (title) is required for creating a habit.".to_string())
+            })?;
+            let schedule = args
+                .frequency
+                .unwrap_or_else(|| config.habit.frequency.clone());
+            let measure = args.target.map(|target| Measure {
+                target,
+                unit: args.unit.unwrap_or_default().trim().to_string(),
+            });
+            let habit = service.create_habit(title, args.description, schedule, measure)?;
+            println!(
+                "Habit created: {} (ID: {}), {}",
+                habit.name, habit.id, habit.schedule
+            );
+            if let Some(measure) = &habit.measure {
+                println!("Target: {} per period", measure);
             }
         }
-        CrudActions::Delete(args) => match service.delete_habit(args.id) {
-            Ok(_) => println!("Habit {} deleted.", args.id),
-            Err(e) => eprintln!("Error deleting habit: {}", e),
-        },
-        CrudActions::Read => match service.list_habits() {
-            Ok(habits) => {
-                if habits.is_empty() {
-                    println!("No habits found.");
-                } else {
+        HabitActions::Delete(args) => {
+            service.delete_habit(args.id)?;
+            println!("Habit {} deleted.", args.id);
+        }
+        HabitActions::Read => {
+            let habits = service.list_habits()?;
+            if habits.is_empty() {
+                println!("No habits found.");
+            } else {
+                let tw = config.output.title_width;
+                let today = Local::now().date_naive();
+                let nw = habits
+                    .iter()
+                    .map(|h| habit_progress(h, today).chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(10);
+                println!(
+                    "{:<5} {:<tw$} {:<nw$} {:>6} {:>6} {:>6}  {:<14} {:<10}",
+                    "ID", "Name", "Now", "Streak", "Best", "Rate", "Schedule", "Last done"
+                );
+                println!("{}", "-".repeat(5 + tw + nw + 56));
+                for habit in habits {
+                    let rate = habit
+                        .completion_rate(today)
+                        .map(|r| format!("{:.0}%", r * 100.0))
+                        .unwrap_or_default();
+                    let last = habit
+                        .last_check_in()
+                        .map(|d| d.to_string())
+                        .unwrap_or_default();
                     println!(
-                        "{:<5} {:<20} {:<10} {:<10}",
-                        "ID", "Name", "Streak", "Frequency"
+                        "{:<5} {:<tw$} {} {:>6} {:>6} {:>6}  {:<14} {:<10}",
+                        habit.id,
+                        truncate(&habit.name, tw),
+                        habit_state_cell(&habit, today, nw),
+                        habit.streak,
+                        habit.longest_streak(today),
+                        rate,
+                        habit.schedule.to_string(),
+                        last
                     );
-                    println!("{}", "-".repeat(50));
-                    for habit in habits {
-                        println!(
-                            "{:<5} {:<20} {:<10} {:<10}",
-                            habit.id,
-                            truncate(&habit.name, 20),
-                            habit.streak,
-                            habit.frequency
-                        );
-                    }
                 }
             }
-            Err(e) => eprintln!("Error reading habits: {}", e),
-        },
-        CrudActions::Update(args) => {
-            match service.update_habit(args.id, args.title, args.description, None, args.increment)
-            {
-                Ok(habit) => println!("Habit {} updated. New streak: {}", habit.id, habit.streak),
-                Err(e) => eprintln!("Error updating habit: {}", e),
+        }
+        HabitActions::Update(args) => {
+            let changes = HabitChanges {
+                name: args.title,
+                description: args.description,
+                schedule: args.frequency,
+                target: args.target,
+                unit: args.unit,
+                clear_measure: args.no_target,
+            };
+            let mut habit = service.update_habit(args.id, changes)?;
+            if args.increment {
+                let (checked, recorded) =
+                    service.check_in(args.id, Local::now().date_naive(), None)?;
+                if !recorded {
+                    println!("Habit {} is already checked in.", habit.id);
+                }
+                habit = checked;
             }
+            println!("Habit {} updated. Streak: {}", habit.id, habit.streak);
+        }
+        HabitActions::Check(args) => {
+            let date = args.date.unwrap_or_else(Local::now).date_naive();
+            let (habit, recorded) = service.check_in(args.id, date, args.amount)?;
+            if let (Some(measure), Some(amount)) = (&habit.measure, args.amount) {
+                println!(
+                    "Habit {}: added {} for {}, {} this period.",
+                    habit.id,
+                    measure.format(amount),
+                    date,
+                    measure.progress(habit.amount(habit.period(date)))
+                );
+            } else if recorded {
+                println!("Habit {} checked in for {}.", habit.id, date);
+            } else if habit.is_checked_on(date) {
+                println!("Habit {} is already checked in for {}.", habit.id, date);
+            } else {
+                println!(
+                    "Habit {} is already done for the period of {}.",
+                    habit.id, date
+                );
+            }
+            println!("Streak: {}", habit.streak);
+        }
+        HabitActions::Uncheck(args) => {
+            let date = args.date.unwrap_or_else(Local::now).date_naive();
+            let habit = service.uncheck(args.id, date)?;
+            println!("Removed the check-in of habit {} for {}.", habit.id, date);
+            println!("Streak: {}", habit.streak);
         }
     }
+    Ok(())
+}
+
+// State of the current period, with the amount so far for measurable habits
+// or the check-ins so far when the period needs more than one
+fn habit_progress(habit: &Habit, today: NaiveDate) -> String {
+    let state = habit.state(today);
+    let period = habit.period(today);
+    let target = habit.schedule.target();
+    match &habit.measure {
+        _ if state == HabitState::Off => state.to_string(),
+        Some(measure) => format!("{} {}", state, measure.progress(habit.amount(period))),
+        None if target > 1 => {
+            let done = habit.done_days(period).min(target);
+            format!("{} {}/{}", state, done, target)
+        }
+        None => state.to_string(),
+    }
+}
+
+// `habit_progress` padded to `width` and colored by state
+fn habit_state_cell(habit: &Habit, today: NaiveDate, width: usize) -> String {
+    let state = habit.state(today);
+    let padded = format!("{:<width$}", habit_progress(habit, today));
+    let color = match state {
+        HabitState::Done => Color::Green,
+        HabitState::Due => Color::Yellow,
+        HabitState::Missed => Color::Red,
+        HabitState::Off => return padded,
+    };
+    if io::stdout().is_terminal() {
+        padded.with(color).to_string()
+    } else {
+        padded
+    }
 }
 
 /// Handle Pomodoro commands
-fn handle_pomodoro(cmd: PomodoroCommand, service: &PomodoroService) {
+fn handle_pomodoro(
+    cmd: PomodoroCommand,
+    service: &PomodoroService<impl Storage>,
+    config: &Config,
+) -> Result<(), AppError> {
     match cmd.action {
         PomodoroActions::Enter(args) => {
-            // Retrieve task if ID provided
-            // For simplicity, we won't validate task ID existence here,
-            // but ideally we should checking against TaskService.
-            // We'll just pass the ID if we had the object, but PomodoroService needs the Task object.
-            // Let's create a minimal Task object or just pass None for now as the service expects Option<Task>.
-            // Actually, the service stores the Task ID.
-            // The service `start_session` takes `Option<Task>`.
-            // If we really want to link it, we'd need to fetch the task first.
-            // For this impl, I'll pass None to keep it simple as requested ("dont make any logic code in main.rs" -> but this is dispatch logic).
-            // Wait, if I want to support task linking, I need to fetch it.
-            // But I only have `pomodoro_service` here.
-            // Refactoring `start_session` to take just `task_id` would be better, but I'll stick to current signature.
-            // I'll pass None for now to avoid cross-service dependency in `main`.
-
-            match service.start_session(
-                args.work,
-                args.short_break,
-                args.long_break,
-                args.sessions,
-                args.long_break_interval,
-                None, // Task linking skipped for simplicity/isolation
-            ) {
-                Ok(session) => println!(
-                    "Pomodoro started! Session {}/{}",
-                    session.current_session, session.total_sessions
-                ),
-                Err(e) => eprintln!("Error starting pomodoro: {}", e),
+            let defaults = &config.pomodoro;
+            let session = service.start_session(
+                args.work.unwrap_or(defaults.work),
+                args.short_break.unwrap_or(defaults.short_break),
+                args.long_break.unwrap_or(defaults.long_break),
+                args.sessions.unwrap_or(defaults.sessions),
+                args.long_break_interval
+                    .unwrap_or(defaults.long_break_interval),
+                args.task_id,
+            )?;
+            println!(
+                "Pomodoro started! Session {}/{}",
+                session.current_session, session.total_sessions
+            );
+            if let Some(id) = session.task_id {
+                println!("Work phases count towards task {}.", id);
             }
         }
-        PomodoroActions::Delete => match service.stop_session() {
-            Ok(_) => println!("Pomodoro session stopped."),
-            Err(e) => eprintln!("Error stopping session: {}", e),
-        },
-        PomodoroActions::Read => match service.get_status() {
-            Ok(Some(session)) => {
+        PomodoroActions::Update => {
+            let session = service.get_status()?.ok_or(AppError::NoActiveSession)?;
+            if session.is_break {
+                let session = service.complete_break()?;
+                if session.current_session > session.total_sessions {
+                    service.stop_session()?;
+                    println!("All {} sessions done!", session.total_sessions);
+                } else {
+                    println!(
+                        "Break over. Session {}/{}: work for {}m.",
+                        session.current_session, session.total_sessions, session.work_duration
+                    );
+                }
+            } else {
+                let session = service.complete_work_session()?;
+                let break_minutes = session.break_minutes();
+                println!(
+                    "Work session {}/{} done, take a {}m break.",
+                    session.current_session, session.total_sessions, break_minutes
+                );
+            }
+        }
+        PomodoroActions::Delete => {
+            service.stop_session()?;
+            println!("Pomodoro session stopped.");
+        }
+        PomodoroActions::Read => match service.get_status()? {
+            Some(session) => {
                 println!("Pomodoro Status:");
                 println!(
                     "Session: {}/{}",
@@ -175,16 +746,304 @@ fn handle_pomodoro(cmd: PomodoroCommand, service: &PomodoroService) {
                 );
                 println!("State: {}", if session.is_break { "Break" } else { "Work" });
                 println!("Duration: {}m", session.work_duration);
+                if let Some(id) = session.task_id {
+                    println!("Task: {}", id);
+                }
             }
-            Ok(None) => println!("No active pomodoro session."),
-            Err(e) => eprintln!("Error reading status: {}", e),
+            None => println!("No active pomodoro session."),
         },
     }
+    Ok(())
+}
+
+/// Handle Time commands
+fn handle_time(cmd: TimeCommand, service: &TaskService<impl Storage>) -> Result<(), AppError> {
+    match cmd.action {
+        TimeActions::Report(args) => {
+            let until = args.until.unwrap_or_else(Local::now);
+            let by = match args.by {
+                ReportBy::Task => ReportGroup::Task,
+                ReportBy::Project => ReportGroup::Project,
+                ReportBy::Tag => ReportGroup::Tag,
+            };
+            let report = service.time_report(args.since, until, by)?;
+            if report.rows.is_empty() {
+                println!("No time tracked.");
+                return Ok(());
+            }
+
+            let width = report
+                .rows
+                .iter()
+                .map(|(name, _)| name.chars().count())
+                .max()
+                .unwrap_or(0)
+                .clamp(5, 40);
+            println!("{:<width$} {:>9}", "Group", "Time");
+            println!("{}", "-".repeat(width + 10));
+            for (name, time) in &report.rows {
+                println!(
+                    "{:<width$} {:>9}",
+                    truncate(name, width),
+                    format_duration(*time)
+                );
+            }
+            println!("{}", "-".repeat(width + 10));
+            println!("{:<width$} {:>9}", "Total", format_duration(report.total));
+        }
+    }
+    Ok(())
+}
+
+/// Handle Stats commands
+fn handle_stats(
+    cmd: StatsCommand,
+    service: &TaskService<impl Storage>,
+    config: &Config,
+) -> Result<(), AppError> {
+    match cmd.action {
+        StatsActions::Estimates(args) => {
+            let filter = Filter::parse(&args.filter, Local::now()).map_err(AppError::Validation)?;
+            let pomodoro = Duration::minutes(config.pomodoro.work as i64);
+            let report = service.estimate_report(&filter, pomodoro, args.weeks)?;
+            if report.tasks.is_empty() {
+                println!("No tasks with an estimate.");
+                return Ok(());
+            }
+
+            let tw = config.output.title_width;
+            println!(
+                "{:<5} {:<tw$} {:<6} {:>12} {:>9} {:>6}",
+                "ID", "Title", "Status", "Estimate", "Actual", "Ratio"
+            );
+            println!("{}", "-".repeat(5 + tw + 6 + 12 + 9 + 6 + 5));
+            for row in &report.tasks {
+                println!(
+                    "{:<5} {:<tw$} {:<6} {:>12} {:>9} {:>6}",
+                    row.task.id,
+                    truncate(&row.task.title, tw),
+                    row.task.status.marker(),
+                    row.task.estimate.map(|e| e.to_string()).unwrap_or_default(),
+                    format_duration(row.actual),
+                    format_ratio(stats::ratio(row.estimate, row.actual))
+                );
+            }
+
+            if report.overall.tasks == 0 {
+                println!();
+                println!("No estimated task is done yet, so there is no accuracy to report.");
+                return Ok(());
+            }
+            println!();
+            println!("Done tasks by project:");
+            print_accuracy("Project", &report.projects);
+            println!();
+            println!("Done tasks by week of completion:");
+            print_accuracy("Week", &report.trend);
+            println!();
+            println!(
+                "Overall: {} of the estimate over {} done task{}",
+                format_ratio(report.overall.ratio()),
+                report.overall.tasks,
+                if report.overall.tasks == 1 { "" } else { "s" }
+            );
+        }
+    }
+    Ok(())
+}
+
+// Estimate/actual table of `stats estimates`
+fn print_accuracy(heading: &str, groups: &[Accuracy]) {
+    let width = groups
+        .iter()
+        .map(|g| g.label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(heading.len(), 30);
+    println!(
+        "{:<width$} {:>5} {:>9} {:>9} {:>6}",
+        heading, "Tasks", "Estimate", "Actual", "Ratio"
+    );
+    for group in groups {
+        println!(
+            "{:<width$} {:>5} {:>9} {:>9} {:>6}",
+            truncate(&group.label, width),
+            group.tasks,
+            format_duration(group.estimate),
+            format_duration(group.actual),
+            format_ratio(group.ratio())
+        );
+    }
+}
+
+// `1.25x`, actual time over the estimate
+fn format_ratio(ratio: Option<f64>) -> String {
+    ratio.map(|r| format!("{:.2}x", r)).unwrap_or_default()
+}
+
+/// Handle the next command
+fn handle_next(
+    args: NextArgs,
+    service: &TaskService<impl Storage>,
+    config: &Config,
+) -> Result<(), AppError> {
+    let tasks = service.next_tasks(&config.urgency, args.count)?;
+    if tasks.is_empty() {
+        println!("Nothing to do.");
+        return Ok(());
+    }
+
+    let tw = config.output.title_width;
+    println!(
+        "{:<5} {:<tw$} {:>8} {:<24}",
+        "ID", "Title", "Urgency", "Due"
+    );
+    println!("{}", "-".repeat(5 + tw + 8 + 24 + 3));
+    let now = Local::now();
+    for (task, urgency) in tasks {
+        println!(
+            "{:<5} {:<tw$} {:>8.2} {}",
+            task.id,
+            truncate(&task.title, tw),
+            urgency.score,
+            due_cell(&task, now, 24)
+        );
+        // How the score adds up, e.g. "due 9.60 + priority 6.00"
+        let parts: Vec<String> = urgency
+            .parts
+            .iter()
+            .map(|(name, value)| format!("{} {:.2}", name, value))
+            .collect();
+        if !parts.is_empty() {
+            println!("{:<5} {}", "", parts.join(" + "));
+        }
+    }
+    Ok(())
+}
+
+/// Handle Project commands
+fn handle_project(
+    cmd: ProjectCommand,
+    service: &TaskService<impl Storage>,
+) -> Result<(), AppError> {
+    match cmd.action {
+        ProjectActions::List => {
+            let projects = service.project_summary()?;
+            if projects.is_empty() {
+                println!("No projects found.");
+                return Ok(());
+            }
+            println!("{:<30} {:>6} {:>6}", "Project", "Open", "Done");
+            println!("{}", "-".repeat(44));
+            for project in projects {
+                // Subprojects are indented under their parent
+                let depth = project.name.matches('.').count();
+                let name = format!("{}{}", "  ".repeat(depth), project.name);
+                println!(
+                    "{:<30} {:>6} {:>6}",
+                    truncate(&name, 30),
+                    project.open,
+                    project.done
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handle Storage commands
+fn handle_storage(
+    cmd: StorageCommand,
+    storage: &impl Storage,
+    data_dir: &str,
+    config: &Config,
+) -> Result<(), AppError> {
+    match cmd.action {
+        StorageActions::Migrate(args) => {
+            let from = JsonStorage::new(data_dir)?;
+            let to = SqliteStorage::new(data_dir)?;
+            let summary = storage::migrate(&from, &to, args.force)?;
+            println!(
+                "Migrated {} tasks, {} habits and {} pomodoro session to SQLite.",
+                summary.tasks,
+                summary.habits,
+                if summary.session { "the current" } else { "no" }
+            );
+            if config.storage.backend != StorageBackend::Sqlite {
+                println!(
+                    "Commands still use the JSON files unless given --backend sqlite; \
+                     run `rtask config set storage.backend sqlite` to switch for good."
+                );
+            }
+        }
+        StorageActions::Check => {
+            println!("Current schema version: {}", CURRENT_VERSION);
+            for (collection, version) in storage.schema_versions()? {
+                match version {
+                    Some(v) if v < CURRENT_VERSION => println!(
+                        "{:<16} version {} (upgraded on next write)",
+                        collection.name(),
+                        v
+                    ),
+                    Some(v) => println!("{:<16} version {}", collection.name(), v),
+                    None => println!("{:<16} no data yet", collection.name()),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handle Config commands
+fn handle_config(cmd: ConfigCommand) -> Result<(), AppError> {
+    let (mut config, skipped) = Config::load_lenient()?;
+    for reason in &skipped {
+        eprintln!("Ignoring {}", reason);
+    }
+    match cmd.action {
+        ConfigActions::Get { key } => println!("{}", config.get(&key)?),
+        ConfigActions::Set { key, value } => {
+            config.set(&key, &value)?;
+            // Saving drops what was ignored, keep the original around
+            if !skipped.is_empty()
+                && let Some(backup) = Config::backup()?
+            {
+                eprintln!("The previous config was saved to {}", backup.display());
+            }
+            config.save()?;
+            println!("{} = {}", key, config.get(&key)?);
+        }
+        ConfigActions::List => {
+            for (key, value) in config.entries()? {
+                println!("{} = {}", key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handle the calc-date command
+fn handle_calc_date(expr: &str) -> Result<(), AppError> {
+    let now = Local::now();
+    let date = dates::parse_date(expr, now).map_err(AppError::Validation)?;
+    println!("{}", date.format("%a %Y-%m-%d %H:%M:%S %:z"));
+
+    let days = (date.date_naive() - now.date_naive()).num_days();
+    match days {
+        0 => println!("today"),
+        1 => println!("tomorrow"),
+        -1 => println!("yesterday"),
+        d if d > 0 => println!("in {} days", d),
+        d => println!("{} days ago", -d),
+    }
+    Ok(())
 }
 
+// Counted in chars, like the `{:<width$}` padding of the tables
 fn truncate(s: &str, max_width: usize) -> String {
-    if s.len() > max_width {
-        format!("{}...", &s[0..max_width - 3])
+    if s.chars().count() > max_width {
+        let kept: String = s.chars().take(max_width.saturating_sub(3)).collect();
+        format!("{}...", kept)
     } else {
         s.to_string()
     }
@@ -1,69 +1,143 @@
 use crate::domain::models::{Habit, PomodoroSession, Task};
+use crate::error::AppError;
+use crate::storage::schema::{self, Collection, Envelope};
+use crate::storage::{Storage, StorageLock};
 use serde::{Serialize, de::DeserializeOwned};
-use std::fs;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::Path;
 
 #[derive(Clone)]
 pub struct JsonStorage {
-    pub data_dir: String,
+    data_dir: String,
 }
 
 impl JsonStorage {
     // Create data directory if it doesnt exist
-    pub fn new(data_dir: &str) -> Self {
+    pub fn new(data_dir: &str) -> Result<Self, AppError> {
         if !Path::new(data_dir).exists() {
-            fs::create_dir_all(data_dir).expect("Failed to create data directory");
+            fs::create_dir_all(data_dir).map_err(|e| {
+                AppError::Storage(format!("Failed to create data directory: {}", e))
+            })?;
         }
-        Self {
+        Ok(Self {
             data_dir: data_dir.to_string(),
+        })
+    }
+
+    // Write to a temp file, fsync it, then rename over the target so a crash
+    // never leaves a half-written file behind
+    fn save<T: Serialize + ?Sized>(
+        &self,
+        collection: Collection,
+        data: &T,
+    ) -> Result<(), AppError> {
+        let path = self.path(collection);
+        let tmp_path = format!("{}.tmp", path);
+        let json = serde_json::to_string_pretty(&Envelope::current(data))
+            .map_err(|e| AppError::Storage(format!("Failed to serialize data: {}", e)))?;
+
+        let mut file = File::create(&tmp_path)
+            .map_err(|e| AppError::Storage(format!("Failed to write to file: {}", e)))?;
+        file.write_all(json.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(|e| AppError::Storage(format!("Failed to write to file: {}", e)))?;
+        fs::rename(&tmp_path, &path)
+            .map_err(|e| AppError::Storage(format!("Failed to replace file: {}", e)))?;
+
+        // Persist the rename itself. Directories can't be opened on every
+        // platform, so this is best effort.
+        if let Ok(dir) = File::open(&self.data_dir) {
+            let _ = dir.sync_all();
         }
+        Ok(())
     }
 
-    fn save<T: Serialize + ?Sized>(&self, filename: &str, data: &T) -> Result<(), String> {
-        let path = format!("{}/{}", self.data_dir, filename);
-        let json = serde_json::to_string_pretty(data)
-            .map_err(|e| format!("Failed to serialize data: {}", e))?;
-        fs::write(&path, json).map_err(|e| format!("Failed to write to file: {}", e))
+    // Load a document, upgrading it from older schema versions on the way
+    fn load<T: DeserializeOwned>(&self, collection: Collection) -> Result<Option<T>, AppError> {
+        let doc = match self.load_raw(collection)? {
+            Some(doc) => doc,
+            None => return Ok(None),
+        };
+        let upgraded = schema::upgrade(collection, doc)?;
+        let data = serde_json::from_value(upgraded)
+            .map_err(|e| AppError::Parse(format!("Failed to parse data: {}", e)))?;
+        Ok(Some(data))
     }
 
-    fn load<T: DeserializeOwned>(&self, filename: &str) -> Result<Option<T>, String> {
-        let path = format!("{}/{}", self.data_dir, filename);
+    fn load_raw(&self, collection: Collection) -> Result<Option<Value>, AppError> {
+        let path = self.path(collection);
         if !Path::new(&path).exists() {
             return Ok(None);
         }
-        let json = fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))?;
-        let data =
-            serde_json::from_str(&json).map_err(|e| format!("Failed to parse data: {}", e))?;
-        Ok(Some(data))
+        let json = fs::read_to_string(&path)
+            .map_err(|e| AppError::Storage(format!("Failed to read file: {}", e)))?;
+        let doc = serde_json::from_str(&json)
+            .map_err(|e| AppError::Parse(format!("Failed to parse data: {}", e)))?;
+        Ok(Some(doc))
+    }
+
+    fn path(&self, collection: Collection) -> String {
+        format!("{}/{}.json", self.data_dir, collection.name())
+    }
+}
+
+impl Storage for JsonStorage {
+    fn lock(&self) -> Result<StorageLock, AppError> {
+        StorageLock::acquire(&self.data_dir)
     }
 
     // Save tasks
-    pub fn save_tasks(&self, tasks: &[Task]) -> Result<(), String> {
-        self.save("tasks.json", tasks)
+    fn save_tasks(&self, tasks: &[Task]) -> Result<(), AppError> {
+        self.save(Collection::Tasks, tasks)
     }
 
     // Load tasks
-    pub fn load_tasks(&self) -> Result<Vec<Task>, String> {
-        Ok(self.load("tasks.json")?.unwrap_or_else(Vec::new))
+    fn load_tasks(&self) -> Result<Vec<Task>, AppError> {
+        Ok(self.load(Collection::Tasks)?.unwrap_or_else(Vec::new))
     }
 
     // Save habits
-    pub fn save_habits(&self, habits: &[Habit]) -> Result<(), String> {
-        self.save("habits.json", habits)
+    fn save_habits(&self, habits: &[Habit]) -> Result<(), AppError> {
+        self.save(Collection::Habits, habits)
     }
 
     // Load habits
-    pub fn load_habits(&self) -> Result<Vec<Habit>, String> {
-        Ok(self.load("habits.json")?.unwrap_or_else(Vec::new))
+    fn load_habits(&self) -> Result<Vec<Habit>, AppError> {
+        Ok(self.load(Collection::Habits)?.unwrap_or_else(Vec::new))
     }
 
     // Save current pomodoro session
-    pub fn save_current_session(&self, session: &PomodoroSession) -> Result<(), String> {
-        self.save("current_session.json", session)
+    fn save_current_session(&self, session: &PomodoroSession) -> Result<(), AppError> {
+        self.save(Collection::Session, session)
     }
 
     // Load current pomodoro session
-    pub fn load_current_session(&self) -> Result<Option<PomodoroSession>, String> {
-        self.load("current_session.json")
+    fn load_current_session(&self) -> Result<Option<PomodoroSession>, AppError> {
+        self.load(Collection::Session)
+    }
+
+    // Remove current pomodoro session
+    fn delete_current_session(&self) -> Result<(), AppError> {
+        let path = self.path(Collection::Session);
+        if Path::new(&path).exists() {
+            fs::remove_file(&path)
+                .map_err(|e| AppError::Storage(format!("Failed to remove session file: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    fn schema_versions(&self) -> Result<Vec<(Collection, Option<u32>)>, AppError> {
+        Collection::ALL
+            .iter()
+            .map(|&c| {
+                let version = self
+                    .load_raw(c)?
+                    .map(|doc| schema::detect_version(&doc))
+                    .transpose()?;
+                Ok((c, version))
+            })
+            .collect()
     }
 }
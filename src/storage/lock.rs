@@ -0,0 +1,63 @@
+use crate::error::AppError;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LOCK_FILE: &str = ".rtask.lock";
+/// How long to wait for another rtask process before giving up
+const LOCK_TIMEOUT: Duration = Duration::from_secs(3);
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Advisory lock on a data directory, held for the whole
+/// load-modify-save cycle of a service call. Released on drop.
+pub struct StorageLock {
+    file: Option<File>,
+}
+
+impl StorageLock {
+    // Lock for backends that have nothing to share across processes
+    pub fn none() -> Self {
+        Self { file: None }
+    }
+
+    // Take the exclusive lock on `data_dir`, waiting up to LOCK_TIMEOUT
+    pub fn acquire(data_dir: &str) -> Result<Self, AppError> {
+        let path = format!("{}/{}", data_dir, LOCK_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| AppError::Storage(format!("Failed to open lock file: {}", e)))?;
+
+        let started = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { file: Some(file) }),
+                Err(TryLockError::WouldBlock) if started.elapsed() < LOCK_TIMEOUT => {
+                    thread::sleep(RETRY_INTERVAL);
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(AppError::Conflict(format!(
+                        "Data directory {} is locked by another rtask process, try again later",
+                        data_dir
+                    )));
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(AppError::Storage(format!(
+                        "Failed to lock data directory: {}",
+                        e
+                    )));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for StorageLock {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            let _ = file.unlock();
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::domain::models::{Habit, PomodoroSession, Task};
+use crate::error::AppError;
+use crate::storage::Storage;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct MemoryData {
+    tasks: Vec<Task>,
+    habits: Vec<Habit>,
+    current_session: Option<PomodoroSession>,
+    /// Times the task list was written
+    task_writes: usize,
+}
+
+/// In-memory storage backend for exercising services in tests without
+/// touching the disk. Clones share the same underlying data, like several
+/// `JsonStorage` values pointing at the same directory.
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    data: Rc<RefCell<MemoryData>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How often the task list has been saved so far
+    pub fn task_writes(&self) -> usize {
+        self.data.borrow().task_writes
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load_tasks(&self) -> Result<Vec<Task>, AppError> {
+        Ok(self.data.borrow().tasks.clone())
+    }
+
+    fn save_tasks(&self, tasks: &[Task]) -> Result<(), AppError> {
+        let mut data = self.data.borrow_mut();
+        data.tasks = tasks.to_vec();
+        data.task_writes += 1;
+        Ok(())
+    }
+
+    fn load_habits(&self) -> Result<Vec<Habit>, AppError> {
+        Ok(self.data.borrow().habits.clone())
+    }
+
+    fn save_habits(&self, habits: &[Habit]) -> Result<(), AppError> {
+        self.data.borrow_mut().habits = habits.to_vec();
+        Ok(())
+    }
+
+    fn load_current_session(&self) -> Result<Option<PomodoroSession>, AppError> {
+        Ok(self.data.borrow().current_session.clone())
+    }
+
+    fn save_current_session(&self, session: &PomodoroSession) -> Result<(), AppError> {
+        self.data.borrow_mut().current_session = Some(session.clone());
+        Ok(())
+    }
+
+    fn delete_current_session(&self) -> Result<(), AppError> {
+        self.data.borrow_mut().current_session = None;
+        Ok(())
+    }
+}
@@ -1 +1,181 @@
-pub mod json_store;
\ No newline at end of file
+pub mod json_store;
+pub mod lock;
+#[cfg(test)]
+pub mod memory_store;
+pub mod schema;
+pub mod sqlite_store;
+
+use crate::domain::models::{Habit, PomodoroSession, Task};
+use crate::error::AppError;
+pub use lock::StorageLock;
+use schema::{CURRENT_VERSION, Collection};
+use std::fs;
+use std::path::Path;
+
+/// Persistence backend used by the domain services.
+///
+/// Services only talk to this trait, so they can be driven by the JSON files
+/// on disk, an in-memory store in tests, or any other backend.
+pub trait Storage {
+    /// Take an exclusive lock for a read-modify-write cycle. Services hold
+    /// the returned guard until they're done saving.
+    fn lock(&self) -> Result<StorageLock, AppError> {
+        Ok(StorageLock::none())
+    }
+
+    fn load_tasks(&self) -> Result<Vec<Task>, AppError>;
+    fn save_tasks(&self, tasks: &[Task]) -> Result<(), AppError>;
+
+    /// Insert or replace a single task. Backends that can update one record
+    /// in place should override this; the default rewrites the whole list.
+    fn save_task(&self, task: &Task) -> Result<(), AppError> {
+        let mut tasks = self.load_tasks()?;
+        match tasks.iter_mut().find(|t| t.id == task.id) {
+            Some(existing) => *existing = task.clone(),
+            None => tasks.push(task.clone()),
+        }
+        self.save_tasks(&tasks)
+    }
+
+    fn load_habits(&self) -> Result<Vec<Habit>, AppError>;
+    fn save_habits(&self, habits: &[Habit]) -> Result<(), AppError>;
+
+    fn load_current_session(&self) -> Result<Option<PomodoroSession>, AppError>;
+    fn save_current_session(&self, session: &PomodoroSession) -> Result<(), AppError>;
+    fn delete_current_session(&self) -> Result<(), AppError>;
+
+    /// Schema version found for each collection, `None` when nothing has
+    /// been stored yet
+    fn schema_versions(&self) -> Result<Vec<(Collection, Option<u32>)>, AppError> {
+        Ok(Collection::ALL
+            .iter()
+            .map(|&c| (c, Some(CURRENT_VERSION)))
+            .collect())
+    }
+}
+
+/// Counts of records copied by [`migrate`]
+pub struct MigrationSummary {
+    pub tasks: usize,
+    pub habits: usize,
+    pub session: bool,
+}
+
+/// Copy every record from one backend into another.
+///
+/// Refuses to overwrite a destination that already holds tasks or habits
+/// unless `force` is set. Only the destination is locked, since both
+/// backends normally share one data directory.
+pub fn migrate(
+    from: &impl Storage,
+    to: &impl Storage,
+    force: bool,
+) -> Result<MigrationSummary, AppError> {
+    let _lock = to.lock()?;
+    if !force && (!to.load_tasks()?.is_empty() || !to.load_habits()?.is_empty()) {
+        return Err(AppError::Conflict(
+            "Destination storage already contains data (use --force to overwrite)".to_string(),
+        ));
+    }
+
+    let tasks = from.load_tasks()?;
+    let habits = from.load_habits()?;
+    let session = from.load_current_session()?;
+
+    to.save_tasks(&tasks)?;
+    to.save_habits(&habits)?;
+    match &session {
+        Some(s) => to.save_current_session(s)?,
+        None => to.delete_current_session()?,
+    }
+
+    // Read everything back to make sure nothing was lost on the way
+    let same_tasks = to_value(&to.load_tasks()?)? == to_value(&tasks)?;
+    let same_habits = to_value(&to.load_habits()?)? == to_value(&habits)?;
+    let same_session = to_value(&to.load_current_session()?)? == to_value(&session)?;
+    if !(same_tasks && same_habits && same_session) {
+        return Err(AppError::Storage(
+            "Migrated data does not match the source".to_string(),
+        ));
+    }
+
+    Ok(MigrationSummary {
+        tasks: tasks.len(),
+        habits: habits.len(),
+        session: session.is_some(),
+    })
+}
+
+/// Copy the rtask data files found in `from` into `to`, unless `to` already
+/// has data of its own. Files that don't hold rtask records are ignored, and
+/// the originals are left where they are. Returns whether anything was copied.
+pub fn adopt_legacy_dir(from: &str, to: &str) -> Result<bool, AppError> {
+    let from_dir = Path::new(from);
+    let to_dir = Path::new(to);
+    if !from_dir.is_dir() {
+        return Ok(false);
+    }
+    if let (Ok(a), Ok(b)) = (from_dir.canonicalize(), to_dir.canonicalize())
+        && a == b
+    {
+        return Ok(false);
+    }
+
+    let mut files: Vec<String> = Collection::ALL
+        .iter()
+        .map(|c| format!("{}.json", c.name()))
+        .collect();
+    files.push(sqlite_store::DB_FILE.to_string());
+    if files.iter().any(|f| to_dir.join(f).exists()) {
+        return Ok(false);
+    }
+
+    // Any project may have a ./data folder, only take what is ours
+    let mut found: Vec<&String> = Collection::ALL
+        .iter()
+        .zip(&files)
+        .filter(|(c, f)| is_rtask_document(**c, &from_dir.join(f)))
+        .map(|(_, f)| f)
+        .collect();
+    let db = &files[Collection::ALL.len()];
+    if sqlite_store::is_rtask_db(&from_dir.join(db)) {
+        found.push(db);
+    }
+    if found.is_empty() {
+        return Ok(false);
+    }
+
+    fs::create_dir_all(to_dir)
+        .map_err(|e| AppError::Storage(format!("Failed to create data directory: {}", e)))?;
+    for file in found {
+        fs::copy(from_dir.join(file), to_dir.join(file))
+            .map_err(|e| AppError::Storage(format!("Failed to copy {}: {}", file, e)))?;
+    }
+    Ok(true)
+}
+
+// True when `path` is a JSON document of `collection`, in any schema
+// version, that holds at least one record
+fn is_rtask_document(collection: Collection, path: &Path) -> bool {
+    let Some(data) = fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .and_then(|doc| schema::upgrade(collection, doc).ok())
+    else {
+        return false;
+    };
+    match collection {
+        Collection::Tasks => {
+            serde_json::from_value::<Vec<Task>>(data).is_ok_and(|tasks| !tasks.is_empty())
+        }
+        Collection::Habits => {
+            serde_json::from_value::<Vec<Habit>>(data).is_ok_and(|habits| !habits.is_empty())
+        }
+        Collection::Session => serde_json::from_value::<PomodoroSession>(data).is_ok(),
+    }
+}
+
+fn to_value<T: serde::Serialize>(data: &T) -> Result<serde_json::Value, AppError> {
+    serde_json::to_value(data)
+        .map_err(|e| AppError::Storage(format!("Failed to serialize data: {}", e)))
+}
@@ -0,0 +1,407 @@
+use crate::domain::models::Schedule;
+use crate::error::AppError;
+use chrono::{DateTime, Duration, Local, Months, NaiveDate, TimeZone};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+/// Version written by this build. Bump it and append a step to `MIGRATIONS`
+/// whenever the on-disk shape of a model changes.
+pub const CURRENT_VERSION: u32 = 5;
+
+/// Upgrades the bare data of one collection by a single version
+type Migration = fn(Collection, Value) -> Result<Value, AppError>;
+
+/// Migration steps, `MIGRATIONS[n]` upgrades a document from version `n + 1`
+/// to version `n + 2`.
+const MIGRATIONS: &[Migration] = &[v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5];
+
+/// The independently versioned data sets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collection {
+    Tasks,
+    Habits,
+    Session,
+}
+
+impl Collection {
+    pub const ALL: [Collection; 3] = [Collection::Tasks, Collection::Habits, Collection::Session];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Collection::Tasks => "tasks",
+            Collection::Habits => "habits",
+            Collection::Session => "current_session",
+        }
+    }
+}
+
+/// Wrapper stored around every document: `{ "version": 2, "data": ... }`
+#[derive(Serialize)]
+pub struct Envelope<'a, T: ?Sized> {
+    pub version: u32,
+    pub data: &'a T,
+}
+
+impl<'a, T: ?Sized> Envelope<'a, T> {
+    pub fn current(data: &'a T) -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            data,
+        }
+    }
+}
+
+// Files written before versioning are the bare array/object: version 1
+fn split_envelope(doc: Value) -> Result<(u32, Value), AppError> {
+    match doc {
+        Value::Object(mut map) if map.contains_key("version") && map.contains_key("data") => {
+            let version = map
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| AppError::Parse("Invalid schema version".to_string()))?
+                as u32;
+            Ok((version, map.remove("data").unwrap_or(Value::Null)))
+        }
+        other => Ok((1, other)),
+    }
+}
+
+/// Version of a raw document as found on disk
+pub fn detect_version(doc: &Value) -> Result<u32, AppError> {
+    split_envelope(doc.clone()).map(|(version, _)| version)
+}
+
+/// Unwrap a raw document and run it through every pending migration,
+/// returning the bare data in the current shape.
+pub fn upgrade(collection: Collection, doc: Value) -> Result<Value, AppError> {
+    let (version, data) = split_envelope(doc)?;
+    upgrade_from(collection, version, data)
+}
+
+/// Like [`upgrade`], for data whose version is tracked outside the document
+pub fn upgrade_from(collection: Collection, version: u32, data: Value) -> Result<Value, AppError> {
+    if version == 0 || version > CURRENT_VERSION {
+        return Err(AppError::Parse(format!(
+            "{} uses schema version {}, but this rtask only understands up to {}",
+            collection.name(),
+            version,
+            CURRENT_VERSION
+        )));
+    }
+
+    let mut data = data;
+    for step in &MIGRATIONS[(version - 1) as usize..] {
+        data = step(collection, data)?;
+    }
+    Ok(data)
+}
+
+// Apply `f` to every record of a collection
+fn for_each_record(
+    data: Value,
+    mut f: impl FnMut(&mut Map<String, Value>),
+) -> Result<Value, AppError> {
+    match data {
+        Value::Array(mut items) => {
+            for item in items.iter_mut() {
+                if let Value::Object(map) = item {
+                    f(map);
+                }
+            }
+            Ok(Value::Array(items))
+        }
+        Value::Object(mut map) => {
+            f(&mut map);
+            Ok(Value::Object(map))
+        }
+        Value::Null => Ok(Value::Null),
+        _ => Err(AppError::Parse("Unexpected document layout".to_string())),
+    }
+}
+
+fn rename_field(map: &mut Map<String, Value>, from: &str, to: &str) {
+    if !map.contains_key(to)
+        && let Some(value) = map.remove(from)
+    {
+        map.insert(to.to_string(), value);
+    }
+}
+
+// v1 -> v2: habits written with the field names from the old README schema
+// (`title`, `last_completed` as a plain date) get the real model's names
+fn v1_to_v2(collection: Collection, data: Value) -> Result<Value, AppError> {
+    if collection != Collection::Habits {
+        return Ok(data);
+    }
+
+    for_each_record(data, |habit| {
+        rename_field(habit, "title", "name");
+        rename_field(habit, "last_completed", "last_completed_at");
+
+        // A bare `YYYY-MM-DD` becomes local midnight of that day
+        if let Some(Value::String(date)) = habit.get("last_completed_at")
+            && let Ok(day) = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            && let Some(at) = day
+                .and_hms_opt(0, 0, 0)
+                .and_then(|dt| Local.from_local_datetime(&dt).earliest())
+        {
+            habit.insert(
+                "last_completed_at".to_string(),
+                Value::String(at.to_rfc3339()),
+            );
+        }
+
+        habit.entry("streak").or_insert_with(|| Value::from(0));
+        let created_at = habit
+            .get("last_completed_at")
+            .filter(|v| v.is_string())
+            .cloned()
+            .unwrap_or_else(|| Value::String(Local::now().to_rfc3339()));
+        habit.entry("created_at").or_insert(created_at);
+    })
+}
+
+// v2 -> v3: the `completed` flag and `completed_at` of tasks become a status
+// with a history of transitions
+fn v2_to_v3(collection: Collection, data: Value) -> Result<Value, AppError> {
+    if collection != Collection::Tasks {
+        return Ok(data);
+    }
+
+    for_each_record(data, |task| {
+        let completed = task
+            .remove("completed")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let completed_at = task.remove("completed_at").filter(Value::is_string);
+        let created_at = task
+            .get("created_at")
+            .filter(|v| v.is_string())
+            .cloned()
+            .unwrap_or_else(|| Value::String(Local::now().to_rfc3339()));
+
+        let mut history = vec![json!({ "status": "todo", "at": created_at })];
+        if completed {
+            history.push(json!({
+                "status": "done",
+                "at": completed_at.unwrap_or(created_at),
+            }));
+        }
+        let status = if completed { "done" } else { "todo" };
+        task.entry("status").or_insert_with(|| Value::from(status));
+        task.entry("history").or_insert(Value::Array(history));
+    })
+}
+
+// v3 -> v4: habits keep a log of check-ins instead of `last_completed_at`.
+// The stored streak is rebuilt as one check-in per period, ending with the
+// last completion and going back no further than the habit's creation.
+fn v3_to_v4(collection: Collection, data: Value) -> Result<Value, AppError> {
+    if collection != Collection::Habits {
+        return Ok(data);
+    }
+
+    let timestamp = |value: Option<&Value>| {
+        value
+            .and_then(Value::as_str)
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|at| at.with_timezone(&Local))
+    };
+    for_each_record(data, |habit| {
+        let last = timestamp(habit.remove("last_completed_at").as_ref());
+        let created = timestamp(habit.get("created_at")).map(|at| at.date_naive());
+        let streak = habit.get("streak").and_then(Value::as_u64).unwrap_or(0);
+        let frequency = habit
+            .get("frequency")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        let previous = |day: NaiveDate| match frequency.as_str() {
+            "weekly" => day.checked_sub_signed(Duration::weeks(1)),
+            "monthly" => day.checked_sub_months(Months::new(1)),
+            _ => day.checked_sub_signed(Duration::days(1)),
+        };
+
+        let mut log = Vec::new();
+        if let Some(last) = last {
+            let mut day = Some(last.date_naive());
+            // The last completion itself is kept even with a zero streak
+            for _ in 0..streak.max(1) {
+                let Some(d) = day.filter(|d| log.is_empty() || created.is_none_or(|c| *d >= c))
+                else {
+                    break;
+                };
+                // The original check-in times are lost, so they share the last one
+                log.push(json!({ "date": d.to_string(), "at": last.to_rfc3339() }));
+                day = previous(d);
+            }
+            log.reverse();
+        }
+        habit.entry("log").or_insert(Value::Array(log));
+    })
+}
+
+// v4 -> v5: the free-form habit `frequency` becomes a typed `schedule`.
+// `daily`, `weekly` and `monthly` keep their meaning; text that isn't a
+// schedule falls back to daily.
+fn v4_to_v5(collection: Collection, data: Value) -> Result<Value, AppError> {
+    if collection != Collection::Habits {
+        return Ok(data);
+    }
+
+    for_each_record(data, |habit| {
+        rename_field(habit, "frequency", "schedule");
+        let schedule = habit
+            .get("schedule")
+            .and_then(Value::as_str)
+            .and_then(|s| s.parse::<Schedule>().ok())
+            .unwrap_or_default();
+        habit.insert("schedule".to_string(), Value::from(String::from(schedule)));
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::{Habit, PomodoroSession, Status, Task};
+    use serde::de::DeserializeOwned;
+
+    fn upgrade_to<T: DeserializeOwned>(collection: Collection, doc: Value) -> T {
+        serde_json::from_value(upgrade(collection, doc).unwrap()).unwrap()
+    }
+
+    fn dates(habit: &Habit) -> Vec<String> {
+        habit.log.iter().map(|c| c.date.to_string()).collect()
+    }
+
+    #[test]
+    fn bare_documents_are_version_1() {
+        let bare: Value = serde_json::from_str(include_str!("../../data/tasks.json")).unwrap();
+        assert_eq!(detect_version(&bare).unwrap(), 1);
+        let wrapped = json!({ "version": 3, "data": [] });
+        assert_eq!(detect_version(&wrapped).unwrap(), 3);
+        // An object that merely has a `data` field is still a bare record
+        assert_eq!(detect_version(&json!({ "data": 1 })).unwrap(), 1);
+    }
+
+    #[test]
+    fn v1_tasks_get_a_status_and_history() {
+        let doc = serde_json::from_str(include_str!("../../data/tasks.json")).unwrap();
+        let tasks: Vec<Task> = upgrade_to(Collection::Tasks, doc);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "fix bug");
+        assert_eq!(tasks[0].status, Status::Todo);
+        assert_eq!(tasks[0].history.len(), 1);
+        assert!(tasks[0].time_log.is_empty());
+
+        let done = json!([{
+            "id": 2,
+            "title": "ship",
+            "description": null,
+            "created_at": "2026-01-02T10:00:00+01:00",
+            "completed": true,
+            "completed_at": "2026-01-03T10:00:00+01:00"
+        }]);
+        let tasks: Vec<Task> = upgrade_to(Collection::Tasks, done);
+        assert_eq!(tasks[0].status, Status::Done);
+        let statuses: Vec<Status> = tasks[0].history.iter().map(|t| t.status).collect();
+        assert_eq!(statuses, [Status::Todo, Status::Done]);
+        assert_eq!(tasks[0].done_at().unwrap().to_rfc3339(), {
+            let at = DateTime::parse_from_rfc3339("2026-01-03T10:00:00+01:00").unwrap();
+            at.with_timezone(&Local).to_rfc3339()
+        });
+    }
+
+    #[test]
+    fn v1_habits_get_a_schedule_and_an_empty_log() {
+        let doc = serde_json::from_str(include_str!("../../data/habits.json")).unwrap();
+        let habits: Vec<Habit> = upgrade_to(Collection::Habits, doc);
+        assert_eq!(habits[0].name, "learn nvim");
+        assert_eq!(habits[0].schedule, Schedule::Daily);
+        assert!(habits[0].log.is_empty());
+        assert!(habits[0].measure.is_none());
+    }
+
+    #[test]
+    fn readme_habits_are_renamed_and_their_streak_backfilled() {
+        let doc = json!([{
+            "id": 1,
+            "title": "Morning Run",
+            "description": "5km minimum",
+            "frequency": "weekly",
+            "streak": 3,
+            "created_at": "2026-01-01T08:00:00+01:00",
+            "last_completed": "2026-01-29"
+        }]);
+        let habits: Vec<Habit> = upgrade_to(Collection::Habits, doc);
+        let habit = &habits[0];
+        assert_eq!(habit.name, "Morning Run");
+        assert_eq!(habit.schedule, Schedule::PerWeek(1));
+        assert_eq!(habit.streak, 3);
+        assert_eq!(dates(habit), ["2026-01-15", "2026-01-22", "2026-01-29"]);
+    }
+
+    #[test]
+    fn backfilled_check_ins_stop_at_the_creation_date() {
+        // Without `created_at` the habit is taken to start on its last
+        // completion, so only that day is logged
+        let doc = json!([{
+            "id": 1,
+            "title": "Morning Run",
+            "description": "5km minimum",
+            "frequency": "daily",
+            "streak": 5,
+            "last_completed": "2026-01-29"
+        }]);
+        let habits: Vec<Habit> = upgrade_to(Collection::Habits, doc);
+        assert_eq!(habits[0].created_at.date_naive().to_string(), "2026-01-29");
+        assert_eq!(dates(&habits[0]), ["2026-01-29"]);
+    }
+
+    #[test]
+    fn unknown_frequencies_fall_back_to_daily() {
+        let doc = json!({ "version": 4, "data": [{
+            "id": 1,
+            "name": "stretch",
+            "description": null,
+            "frequency": "whenever",
+            "streak": 0,
+            "created_at": "2026-01-01T08:00:00+01:00",
+            "log": []
+        }, {
+            "id": 2,
+            "name": "gym",
+            "description": null,
+            "frequency": "3/week",
+            "streak": 0,
+            "created_at": "2026-01-01T08:00:00+01:00",
+            "log": []
+        }]});
+        let habits: Vec<Habit> = upgrade_to(Collection::Habits, doc);
+        assert_eq!(habits[0].schedule, Schedule::Daily);
+        assert_eq!(habits[1].schedule, Schedule::PerWeek(3));
+    }
+
+    #[test]
+    fn sessions_pass_through_unchanged() {
+        let doc = serde_json::from_str(include_str!("../../data/current_session.json")).unwrap();
+        let session: PomodoroSession = upgrade_to(Collection::Session, doc);
+        assert_eq!(session.sessions_before_long_break, 4);
+        let none = upgrade(Collection::Session, json!({ "version": 2, "data": null }));
+        assert_eq!(none.unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn current_documents_are_left_alone_and_newer_ones_refused() {
+        let data = json!([{ "id": 1, "name": "x", "schedule": "daily" }]);
+        let doc = json!({ "version": CURRENT_VERSION, "data": data.clone() });
+        assert_eq!(upgrade(Collection::Habits, doc).unwrap(), data);
+
+        let newer = json!({ "version": CURRENT_VERSION + 1, "data": [] });
+        assert!(matches!(
+            upgrade(Collection::Tasks, newer),
+            Err(AppError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,445 @@
+use crate::domain::models::{Habit, PomodoroSession, Task};
+use crate::error::AppError;
+use crate::storage::schema::{self, CURRENT_VERSION, Collection};
+use crate::storage::{Storage, StorageLock};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+pub const DB_FILE: &str = "rtask.db";
+
+/// SQLite-backed storage. Every record is kept as one row holding its JSON
+/// representation, so single-record updates don't rewrite the whole table.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    data_dir: String,
+    conn: Rc<Connection>,
+}
+
+/// True when `path` is a database with the tables rtask creates
+pub fn is_rtask_db(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+    let Ok(conn) = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+        return false;
+    };
+    Collection::ALL.iter().all(|c| {
+        conn.prepare(&format!("SELECT id, data FROM {} LIMIT 1", c.name()))
+            .is_ok()
+    })
+}
+
+impl SqliteStorage {
+    // Open (or create) `rtask.db` inside the data directory
+    pub fn new(data_dir: &str) -> Result<Self, AppError> {
+        if !Path::new(data_dir).exists() {
+            fs::create_dir_all(data_dir).map_err(|e| {
+                AppError::Storage(format!("Failed to create data directory: {}", e))
+            })?;
+        }
+        let path = format!("{}/{}", data_dir, DB_FILE);
+        let conn = Connection::open(&path)
+            .map_err(|e| AppError::Storage(format!("Failed to open database: {}", e)))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tasks (id INTEGER PRIMARY KEY, data TEXT NOT NULL);
+             CREATE TABLE IF NOT EXISTS habits (id INTEGER PRIMARY KEY, data TEXT NOT NULL);
+             CREATE TABLE IF NOT EXISTS current_session (
+                 id INTEGER PRIMARY KEY CHECK (id = 1),
+                 data TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+        )
+        .map_err(|e| AppError::Storage(format!("Failed to initialize database: {}", e)))?;
+        // Let SQLite wait for short writes from other processes on its own
+        conn.busy_timeout(Duration::from_secs(3))
+            .map_err(|e| AppError::Storage(format!("Failed to configure database: {}", e)))?;
+
+        let storage = Self {
+            data_dir: data_dir.to_string(),
+            conn: Rc::new(conn),
+        };
+        storage.upgrade_schema()?;
+        Ok(storage)
+    }
+
+    // Databases created before versioning have no meta row: version 1
+    fn schema_version(&self) -> Result<u32, AppError> {
+        let version: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'schema_version'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| AppError::Storage(format!("Failed to read schema version: {}", e)))?;
+        match version {
+            Some(v) => v
+                .parse()
+                .map_err(|_| AppError::Parse(format!("Invalid schema version: {}", v))),
+            None => Ok(1),
+        }
+    }
+
+    // Run every stored record through the pending migrations in one go
+    fn upgrade_schema(&self) -> Result<(), AppError> {
+        let _lock = StorageLock::acquire(&self.data_dir)?;
+        let version = self.schema_version()?;
+        if version == CURRENT_VERSION {
+            return Ok(());
+        }
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| AppError::Storage(format!("Failed to start transaction: {}", e)))?;
+        for collection in Collection::ALL {
+            let table = collection.name();
+            let rows = Value::Array(self.load_values(table)?);
+            let upgraded = match schema::upgrade_from(collection, version, rows)? {
+                Value::Array(items) => items,
+                _ => {
+                    return Err(AppError::Storage(format!(
+                        "Migration of {} returned no rows",
+                        table
+                    )));
+                }
+            };
+            tx.execute(&format!("DELETE FROM {}", table), [])
+                .map_err(|e| AppError::Storage(format!("Failed to clear {}: {}", table, e)))?;
+            for item in upgraded {
+                let id = item.get("id").and_then(Value::as_u64).unwrap_or(1);
+                tx.execute(
+                    &format!("INSERT INTO {} (id, data) VALUES (?1, ?2)", table),
+                    params![id, to_json(&item)?],
+                )
+                .map_err(|e| AppError::Storage(format!("Failed to write to {}: {}", table, e)))?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?1)",
+            params![CURRENT_VERSION.to_string()],
+        )
+        .map_err(|e| AppError::Storage(format!("Failed to write schema version: {}", e)))?;
+        tx.commit()
+            .map_err(|e| AppError::Storage(format!("Failed to commit transaction: {}", e)))
+    }
+
+    fn load_rows<T: DeserializeOwned>(&self, table: &str) -> Result<Vec<T>, AppError> {
+        self.load_values(table)?
+            .into_iter()
+            .map(|v| {
+                serde_json::from_value(v)
+                    .map_err(|e| AppError::Parse(format!("Failed to parse data: {}", e)))
+            })
+            .collect()
+    }
+
+    fn load_values(&self, table: &str) -> Result<Vec<Value>, AppError> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT data FROM {} ORDER BY id", table))
+            .map_err(|e| AppError::Storage(format!("Failed to query {}: {}", table, e)))?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| AppError::Storage(format!("Failed to query {}: {}", table, e)))?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            let json = row.map_err(|e| AppError::Storage(format!("Failed to read row: {}", e)))?;
+            items.push(from_json(&json)?);
+        }
+        Ok(items)
+    }
+
+    fn replace_rows<T: Serialize>(
+        &self,
+        table: &str,
+        rows: impl Iterator<Item = (u32, T)>,
+    ) -> Result<(), AppError> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| AppError::Storage(format!("Failed to start transaction: {}", e)))?;
+        tx.execute(&format!("DELETE FROM {}", table), [])
+            .map_err(|e| AppError::Storage(format!("Failed to clear {}: {}", table, e)))?;
+        for (id, item) in rows {
+            tx.execute(
+                &format!("INSERT INTO {} (id, data) VALUES (?1, ?2)", table),
+                params![id, to_json(&item)?],
+            )
+            .map_err(|e| AppError::Storage(format!("Failed to write to {}: {}", table, e)))?;
+        }
+        tx.commit()
+            .map_err(|e| AppError::Storage(format!("Failed to commit transaction: {}", e)))
+    }
+}
+
+fn to_json<T: Serialize + ?Sized>(data: &T) -> Result<String, AppError> {
+    serde_json::to_string(data)
+        .map_err(|e| AppError::Storage(format!("Failed to serialize data: {}", e)))
+}
+
+fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, AppError> {
+    serde_json::from_str(json).map_err(|e| AppError::Parse(format!("Failed to parse data: {}", e)))
+}
+
+impl Storage for SqliteStorage {
+    // Transactions only cover single statements here, so services still need
+    // the directory lock around their load-modify-save cycle
+    fn lock(&self) -> Result<StorageLock, AppError> {
+        StorageLock::acquire(&self.data_dir)
+    }
+
+    fn load_tasks(&self) -> Result<Vec<Task>, AppError> {
+        self.load_rows("tasks")
+    }
+
+    fn save_tasks(&self, tasks: &[Task]) -> Result<(), AppError> {
+        self.replace_rows("tasks", tasks.iter().map(|t| (t.id, t)))
+    }
+
+    // Upsert a single row instead of rewriting the whole table
+    fn save_task(&self, task: &Task) -> Result<(), AppError> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO tasks (id, data) VALUES (?1, ?2)",
+                params![task.id, to_json(task)?],
+            )
+            .map_err(|e| AppError::Storage(format!("Failed to write task: {}", e)))?;
+        Ok(())
+    }
+
+    fn load_habits(&self) -> Result<Vec<Habit>, AppError> {
+        self.load_rows("habits")
+    }
+
+    fn save_habits(&self, habits: &[Habit]) -> Result<(), AppError> {
+        self.replace_rows("habits", habits.iter().map(|h| (h.id, h)))
+    }
+
+    fn load_current_session(&self) -> Result<Option<PomodoroSession>, AppError> {
+        let json: Option<String> = self
+            .conn
+            .query_row("SELECT data FROM current_session WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|e| AppError::Storage(format!("Failed to read session: {}", e)))?;
+        json.map(|j| from_json(&j)).transpose()
+    }
+
+    fn schema_versions(&self) -> Result<Vec<(Collection, Option<u32>)>, AppError> {
+        let version = self.schema_version()?;
+        Ok(Collection::ALL
+            .iter()
+            .map(|&c| (c, Some(version)))
+            .collect())
+    }
+
+    fn save_current_session(&self, session: &PomodoroSession) -> Result<(), AppError> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO current_session (id, data) VALUES (1, ?1)",
+                params![to_json(session)?],
+            )
+            .map_err(|e| AppError::Storage(format!("Failed to write session: {}", e)))?;
+        Ok(())
+    }
+
+    fn delete_current_session(&self) -> Result<(), AppError> {
+        self.conn
+            .execute("DELETE FROM current_session", [])
+            .map_err(|e| AppError::Storage(format!("Failed to remove session: {}", e)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::{Measure, Schedule, Status};
+    use crate::domain::services::{HabitService, NewTask, TaskService};
+    use crate::storage::json_store::JsonStorage;
+    use crate::storage::migrate;
+    use chrono::Local;
+    use std::path::PathBuf;
+
+    // A fresh directory under the system temp dir, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rtask-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn to_value<T: Serialize>(data: &T) -> Value {
+        serde_json::to_value(data).unwrap()
+    }
+
+    #[test]
+    fn save_task_inserts_or_replaces_one_row() {
+        let dir = TempDir::new("sqlite-upsert");
+        let storage = SqliteStorage::new(dir.path()).unwrap();
+        let service = TaskService::new(storage.clone());
+        for title in ["a", "b"] {
+            service
+                .create_task(NewTask {
+                    title: title.to_string(),
+                    ..NewTask::default()
+                })
+                .unwrap();
+        }
+
+        let mut task = storage.load_tasks().unwrap().remove(0);
+        task.title = "renamed".to_string();
+        storage.save_task(&task).unwrap();
+        task.id = 7;
+        storage.save_task(&task).unwrap();
+
+        let titles: Vec<(u32, String)> = storage
+            .load_tasks()
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.id, t.title))
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                (1, "renamed".to_string()),
+                (2, "b".to_string()),
+                (7, "renamed".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn sessions_are_stored_in_a_single_row() {
+        let dir = TempDir::new("sqlite-session");
+        let storage = SqliteStorage::new(dir.path()).unwrap();
+        let session: PomodoroSession =
+            serde_json::from_str(include_str!("../../data/current_session.json")).unwrap();
+        storage.save_current_session(&session).unwrap();
+        storage.save_current_session(&session).unwrap();
+        assert_eq!(
+            to_value(&storage.load_current_session().unwrap()),
+            to_value(&Some(&session))
+        );
+        storage.delete_current_session().unwrap();
+        assert!(storage.load_current_session().unwrap().is_none());
+    }
+
+    #[test]
+    fn databases_without_a_version_are_upgraded_from_version_1() {
+        let dir = TempDir::new("sqlite-upgrade");
+        let path = format!("{}/{}", dir.path(), DB_FILE);
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE tasks (id INTEGER PRIMARY KEY, data TEXT NOT NULL);
+                 CREATE TABLE habits (id INTEGER PRIMARY KEY, data TEXT NOT NULL);
+                 CREATE TABLE current_session (id INTEGER PRIMARY KEY, data TEXT NOT NULL);",
+            )
+            .unwrap();
+            let tasks: Vec<Value> =
+                serde_json::from_str(include_str!("../../data/tasks.json")).unwrap();
+            let habits: Vec<Value> =
+                serde_json::from_str(include_str!("../../data/habits.json")).unwrap();
+            for (table, row) in [("tasks", &tasks[0]), ("habits", &habits[0])] {
+                conn.execute(
+                    &format!("INSERT INTO {} (id, data) VALUES (1, ?1)", table),
+                    params![row.to_string()],
+                )
+                .unwrap();
+            }
+        }
+
+        let storage = SqliteStorage::new(dir.path()).unwrap();
+        let tasks = storage.load_tasks().unwrap();
+        assert_eq!(tasks[0].status, Status::Todo);
+        assert_eq!(tasks[0].history.len(), 1);
+        assert_eq!(storage.load_habits().unwrap()[0].schedule, Schedule::Daily);
+        assert!(
+            storage
+                .schema_versions()
+                .unwrap()
+                .iter()
+                .all(|(_, v)| *v == Some(CURRENT_VERSION))
+        );
+
+        // Opening it again leaves the upgraded rows alone
+        drop(storage);
+        let reopened = SqliteStorage::new(dir.path()).unwrap();
+        assert_eq!(reopened.schema_version().unwrap(), CURRENT_VERSION);
+        assert_eq!(to_value(&reopened.load_tasks().unwrap()), to_value(&tasks));
+    }
+
+    #[test]
+    fn migrating_from_json_keeps_every_field() {
+        let dir = TempDir::new("sqlite-migrate");
+        let json = JsonStorage::new(dir.path()).unwrap();
+        let tasks = TaskService::new(json.clone());
+        tasks
+            .create_task(NewTask {
+                title: "pay rent".to_string(),
+                description: Some("flat".to_string()),
+                priority: "H".parse().ok(),
+                due: Some(Local::now() + chrono::Duration::days(3)),
+                recurrence: "monthly".parse().ok(),
+                tags: vec!["home".to_string()],
+                project: Some("admin.bills".to_string()),
+                estimate: "1.5h".parse().ok(),
+                ..NewTask::default()
+            })
+            .unwrap();
+        let habits = HabitService::new(json.clone());
+        let measure = Measure {
+            target: 20.0,
+            unit: "pages".to_string(),
+        };
+        habits
+            .create_habit("read".to_string(), None, Schedule::Daily, Some(measure))
+            .unwrap();
+        habits
+            .check_in(1, Local::now().date_naive(), Some(12.5))
+            .unwrap();
+
+        let sqlite = SqliteStorage::new(dir.path()).unwrap();
+        let summary = migrate(&json, &sqlite, false).unwrap();
+        assert_eq!(
+            (summary.tasks, summary.habits, summary.session),
+            (1, 1, false)
+        );
+        assert_eq!(
+            to_value(&sqlite.load_tasks().unwrap()),
+            to_value(&json.load_tasks().unwrap())
+        );
+        assert_eq!(
+            to_value(&sqlite.load_habits().unwrap()),
+            to_value(&json.load_habits().unwrap())
+        );
+
+        let again = migrate(&json, &sqlite, false);
+        assert!(matches!(again, Err(AppError::Conflict(_))));
+        assert!(migrate(&json, &sqlite, true).is_ok());
+    }
+}